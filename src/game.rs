@@ -0,0 +1,708 @@
+// Import Shapes structs and Screen struct
+use crate::gravity;
+use crate::input::Held;
+use crate::score::{self, Clear, Spin};
+use crate::shape::{Shape, Shapes};
+use crate::{DasCharge, Handling, Key, LockReset, Rng, RotationSystem, Row, Ruleset, Screen};
+
+// Longest next queue
+pub const MAX_NEXT: usize = 6;
+// Shapes spawn in the 2 rows above the visible field
+const SPAWN_ROWS: usize = 2;
+
+// Whole game state
+// (no io here, frontend reads fields and calls actions)
+pub struct Game<R: Row = u16> {
+    pub screen: Screen<R>,
+    pub current_shape: Shape,
+    // Upcoming shapes, next_shapes() are the shown ones
+    next_shapes: [Shapes; MAX_NEXT],
+    // Held shape and can it be swapped now (once per drop)
+    pub hold_shape: Option<Shapes>,
+    pub can_hold: bool,
+    pub scores: usize,
+    pub lines: usize,
+    pub level: usize,
+    // Line clears in a row (-1 after a shape without lines)
+    pub combo: i32,
+    // Last line clear was difficult, the next difficult one gets bonus
+    pub back_to_back: bool,
+    pub last_clear: Option<Clear>,
+    // Frames since the last clear (frontends show it for a while)
+    pub clear_frames: u32,
+    pub over: bool,
+    // Seed of this game, same seed replays the same shapes
+    pub seed: u64,
+    pub rules: Ruleset,
+    pub handling: Handling,
+    held: Held,
+    rng: Rng,
+    // Frames on the ground, lock delay resets used, lowest row reached
+    lock_frames: u32,
+    lock_resets: u32,
+    lowest_y: i32,
+    // Fallen part of the next row (in gravity::ROW parts)
+    fall: u32,
+    // Kick of the rotation when it was the last move (for T-spins)
+    last_kick: Option<usize>,
+}
+
+impl<R: Row> Game<R> {
+    // Visible field is width * height, the same count of rows is hidden above it
+    // (width is up to the bits of R)
+    pub fn new(width: usize, height: usize, seed: u64, mut rules: Ruleset) -> Self {
+        let mut rng = Rng::new(seed);
+        rules.next = rules.next.clamp(1, MAX_NEXT);
+        let screen = Screen::new(width, 2 * height, height);
+        let current_shape = rules.randomizer.next(&mut rng);
+        let current_shape = spawn_shape(&screen, current_shape, rules.rotation);
+        let mut next_shapes = [Shapes::Tshape; MAX_NEXT];
+        for shape in next_shapes[..rules.next].iter_mut() {
+            *shape = rules.randomizer.next(&mut rng);
+        }
+        let lowest_y = current_shape.y;
+        Self {
+            screen,
+            current_shape,
+            next_shapes,
+            hold_shape: None,
+            can_hold: true,
+            scores: 0,
+            lines: 0,
+            level: rules.start_level,
+            combo: -1,
+            back_to_back: false,
+            last_clear: None,
+            clear_frames: 0,
+            over: false,
+            seed,
+            rules,
+            handling: Handling::default(),
+            held: Held::default(),
+            rng,
+            lock_frames: 0,
+            lock_resets: 0,
+            lowest_y,
+            fall: 0,
+            last_kick: None,
+        }
+    }
+
+    pub fn next_shapes(&self) -> &[Shapes] {
+        &self.next_shapes[..self.rules.next]
+    }
+
+    // Take the first shape of the queue and fill the queue
+    fn new_shape(&mut self) -> Shape {
+        let count = self.rules.next;
+        let shape = self.next_shapes[0];
+        self.next_shapes.copy_within(1..count, 0);
+        self.next_shapes[count - 1] = self.rules.randomizer.next(&mut self.rng);
+        spawn_shape(&self.screen, shape, self.rules.rotation)
+    }
+
+    // Key is pressed down
+    pub fn press(&mut self, key: Key) {
+        match key {
+            Key::Left | Key::Right => {
+                let side = if key == Key::Left { -1 } else { 1 };
+                if key == Key::Left {
+                    self.held.left = true;
+                } else {
+                    self.held.right = true;
+                }
+                self.held.side = side;
+                self.held.das_frames = 0;
+                self.held.arr_frames = 0;
+                self.move_side(side);
+            }
+            Key::SoftDrop => {
+                self.held.soft_drop = true;
+                self.held.soft_drop_frames = 0;
+                self.soft_drop();
+            }
+            Key::HardDrop => self.hard_drop(),
+            Key::RotateCw => self.rotate(1),
+            Key::RotateCcw => self.rotate(-1),
+            Key::Hold => self.hold(),
+        }
+    }
+
+    // Key turned out to be held for frames already, without a new move
+    // (frontends which see held keys late; a charged DAS slides next frame)
+    pub fn press_held(&mut self, key: Key, frames: u32) {
+        match key {
+            Key::Left | Key::Right => {
+                if key == Key::Left {
+                    self.held.left = true;
+                } else {
+                    self.held.right = true;
+                }
+                self.held.side = if key == Key::Left { -1 } else { 1 };
+                self.held.das_frames = frames.min(self.handling.das.saturating_sub(1));
+                self.held.arr_frames = 0;
+            }
+            Key::SoftDrop => {
+                self.held.soft_drop = true;
+                self.held.soft_drop_frames = 0;
+            }
+            _ => (),
+        }
+    }
+
+    // Key is released
+    // (sliding goes on to the other side when it is still held)
+    pub fn release(&mut self, key: Key) {
+        match key {
+            Key::Left | Key::Right => {
+                let (side, other_held) = if key == Key::Left {
+                    self.held.left = false;
+                    (-1, self.held.right)
+                } else {
+                    self.held.right = false;
+                    (1, self.held.left)
+                };
+                if self.held.side == side {
+                    self.held.side = if other_held { -side } else { 0 };
+                    self.held.das_frames = 0;
+                    self.held.arr_frames = 0;
+                }
+            }
+            Key::SoftDrop => self.held.soft_drop = false,
+            _ => (),
+        }
+    }
+
+    // DAS, ARR and soft drop of held keys (one frame)
+    fn repeat_held(&mut self) {
+        let side = self.held.side;
+        if side != 0 {
+            let das = self.handling.das;
+            if self.held.das_frames < das {
+                self.held.das_frames += 1;
+                if self.held.das_frames == das {
+                    // DAS charged: first slide now, rest by ARR
+                    // (ARR 0 goes to the wall in this frame)
+                    self.held.arr_frames = 0;
+                    if self.handling.arr == 0 {
+                        while self.move_side(side) {}
+                    } else {
+                        self.move_side(side);
+                    }
+                }
+            } else {
+                self.held.arr_frames += 1;
+                let arr = self.handling.arr;
+                if arr == 0 {
+                    while self.move_side(side) {}
+                } else if self.held.arr_frames >= arr {
+                    self.held.arr_frames = 0;
+                    self.move_side(side);
+                }
+            }
+        }
+
+        if self.held.soft_drop {
+            self.held.soft_drop_frames += 1;
+            if self.held.soft_drop_frames >= self.handling.soft_drop {
+                self.held.soft_drop_frames = 0;
+                self.soft_drop();
+            }
+        }
+    }
+
+    // Move figure left/right (true if it moved)
+    pub fn move_side(&mut self, side: i32) -> bool {
+        if self.over {
+            return false;
+        }
+        let mut shape = self.current_shape;
+        shape.x += side;
+        if self.screen.updatable(&shape).is_ok() {
+            self.current_shape = shape;
+            self.last_kick = None;
+            self.reset_lock_delay();
+            return true;
+        }
+        false
+    }
+
+    // Rotate figure
+    // (with kicks of the rotation system of the rules)
+    pub fn rotate(&mut self, rotate: i32) {
+        if self.over {
+            return;
+        }
+        let system = self.rules.rotation;
+        if let Some((shape, kick)) = system.rotate(&self.screen, &self.current_shape, rotate) {
+            self.current_shape = shape;
+            self.last_kick = Some(kick);
+            self.reset_lock_delay();
+        }
+    }
+
+    // Time goes one frame (1 / 60 s)
+    // (held keys repeat, shape falls by gravity of the level
+    // and on the ground locks after the lock delay)
+    pub fn tick(&mut self) {
+        if self.over {
+            return;
+        }
+        self.clear_frames = self.clear_frames.saturating_add(1);
+        self.repeat_held();
+        self.fall();
+        if self.over || self.rules.lock_delay == 0 || !self.grounded() {
+            return;
+        }
+        self.lock_frames += 1;
+        if self.lock_frames >= self.rules.lock_delay {
+            self.lock();
+        }
+    }
+
+    // Gravity of the level moves shape down
+    // (on the ground without lock delay the next row locks it)
+    fn fall(&mut self) {
+        if self.over {
+            return;
+        }
+        self.fall += self.rules.gravity.gravity(self.level);
+        while self.fall >= gravity::ROW {
+            self.fall -= gravity::ROW;
+            if !self.move_down() {
+                self.fall = 0;
+                return;
+            }
+        }
+    }
+
+    // Update game loop
+    // (move shape down, without lock delay lock it when it can't fall;
+    // true if it moved)
+    pub fn move_down(&mut self) -> bool {
+        if self.over {
+            return false;
+        }
+        let mut shape = self.current_shape;
+        shape.y += 1;
+        if self.screen.updatable(&shape).is_ok() {
+            self.current_shape = shape;
+            self.last_kick = None;
+            if self.current_shape.y > self.lowest_y {
+                self.lowest_y = self.current_shape.y;
+                self.lock_resets = 0;
+                self.lock_frames = 0;
+            } else if !matches!(self.rules.lock_reset, LockReset::Move(_)) {
+                // (limited resets: falling back after a kick up is no reset)
+                self.lock_frames = 0;
+            }
+            return true;
+        }
+
+        if self.rules.lock_delay == 0 {
+            self.lock();
+        }
+        false
+    }
+
+    // Is shape on the ground?
+    pub fn grounded(&self) -> bool {
+        let mut shape = self.current_shape;
+        shape.y += 1;
+        self.screen.updatable(&shape).is_err()
+    }
+
+    // Move or rotation restarts lock delay (if the rules allow)
+    fn reset_lock_delay(&mut self) {
+        match self.rules.lock_reset {
+            LockReset::Move(limit) => {
+                if self.lock_resets < limit {
+                    self.lock_resets += 1;
+                    self.lock_frames = 0;
+                }
+            }
+            LockReset::Step => (),
+            LockReset::Infinite => self.lock_frames = 0,
+        }
+    }
+
+    // Move shape down by player (1 point per cell)
+    pub fn soft_drop(&mut self) {
+        if self.move_down() {
+            self.scores += score::SOFT_DROP;
+        }
+    }
+
+    // Drop shape to the bottom and lock it (2 points per cell)
+    pub fn hard_drop(&mut self) {
+        if self.over {
+            return;
+        }
+        let cells = self.drop_distance();
+        self.current_shape.y += cells;
+        if cells > 0 {
+            self.last_kick = None;
+        }
+        self.scores += score::HARD_DROP * cells as usize;
+        self.lock();
+    }
+
+    // How many rows the shape can fall
+    pub fn drop_distance(&self) -> i32 {
+        let mut shape = self.current_shape;
+        let mut cells = 0;
+        loop {
+            shape.y += 1;
+            if self.screen.updatable(&shape).is_err() {
+                return cells;
+            }
+            cells += 1;
+        }
+    }
+
+    // Where the falling shape will land
+    pub fn ghost_shape(&self) -> Shape {
+        let mut shape = self.current_shape;
+        shape.y += self.drop_distance();
+        shape
+    }
+
+    // Put shape to screen, clear lines and take the next shape
+    // (lock out: game over when the shape is fully above the visible field,
+    // or partly with partial lock out rule)
+    fn lock(&mut self) {
+        let shape = &self.current_shape;
+        let (top, bottom) = shape.cells_rows();
+        let hidden = self.screen.hidden as i32;
+        if bottom < hidden || (self.rules.partial_lock_out && top < hidden) {
+            self.screen.put(shape);
+            self.over = true;
+            return;
+        }
+
+        let spin = score::t_spin(&self.screen, &self.current_shape, self.last_kick);
+        self.screen.put(&self.current_shape);
+        let lines = self.screen.clear_lines(self.rules.line_gravity);
+        self.score_clear(lines, spin);
+        self.lines += lines;
+        self.level = self.rules.start_level + self.lines / self.rules.lines_per_level.max(1);
+
+        let next_shape = self.new_shape();
+        self.spawn(next_shape);
+        self.can_hold = true;
+    }
+
+    // Points, combo and back-to-back of cleared lines
+    // (T-spin without lines scores too but keeps back-to-back as is)
+    fn score_clear(&mut self, lines: usize, spin: Spin) {
+        if lines == 0 {
+            self.combo = -1;
+            if spin == Spin::No {
+                return;
+            }
+        } else {
+            self.combo += 1;
+        }
+        let difficult = score::difficult(lines, spin);
+        let back_to_back = difficult && self.back_to_back;
+        if lines > 0 {
+            self.back_to_back = difficult;
+        }
+
+        let combo = self.combo.max(0) as u32;
+        let mut points = score::points(lines, spin, back_to_back, combo, self.level);
+        let perfect = lines > 0 && self.screen.is_empty();
+        if perfect {
+            points += score::perfect_clear(lines, back_to_back, self.level);
+        }
+        self.scores += points;
+        self.clear_frames = 0;
+        self.last_clear = Some(Clear {
+            lines,
+            spin,
+            perfect,
+            back_to_back,
+            combo,
+            points,
+        });
+    }
+
+    // Swap falling shape with the held one
+    // (or with the next one when nothing is held yet)
+    pub fn hold(&mut self) {
+        if self.over || !self.rules.hold || !self.can_hold {
+            return;
+        }
+        let shape = match self.hold_shape {
+            Some(held) => spawn_shape(&self.screen, held, self.rules.rotation),
+            None => self.new_shape(),
+        };
+        self.hold_shape = Some(self.current_shape.shape);
+        self.spawn(shape);
+        self.can_hold = false;
+    }
+
+    // New falling shape, game over when it can't be putted (block out)
+    fn spawn(&mut self, shape: Shape) {
+        self.lowest_y = shape.y;
+        self.fall = 0;
+        self.lock_frames = 0;
+        self.lock_resets = 0;
+        self.last_kick = None;
+        if self.handling.das_charge == DasCharge::Reset {
+            self.held.das_frames = 0;
+            self.held.arr_frames = 0;
+        }
+        self.current_shape = shape;
+        if self.screen.updatable(&self.current_shape).is_err() {
+            self.over = true;
+        }
+    }
+}
+
+// Shape in the spawn rows above the visible field
+fn spawn_shape<R: Row>(screen: &Screen<R>, shape: Shapes, system: RotationSystem) -> Shape {
+    let mut shape = Shape::new(screen.width as i32, shape, system);
+    shape.y += screen.hidden.saturating_sub(SPAWN_ROWS) as i32;
+    shape
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Spin;
+
+    fn game() -> Game {
+        Game::new(10, 20, 1, Ruleset::guideline())
+    }
+
+    // Fill row y, but the holes
+    fn fill(game: &mut Game, y: usize, holes: &[usize]) {
+        for x in 0..game.screen.width {
+            if !holes.contains(&x) {
+                game.screen.set(x, y, Shapes::Oshape);
+            }
+        }
+    }
+
+    // Make shape falling at (x; y) in rotation state
+    fn place(game: &mut Game, shape: Shapes, rotate: i32, x: i32, y: i32) {
+        let system = game.rules.rotation;
+        let mut shape = Shape::new(10, shape, system).rotate(rotate, system);
+        shape.x = x;
+        shape.y = y;
+        game.current_shape = shape;
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let (mut a, mut b) = (game(), game());
+        for _ in 0..10 {
+            a.hard_drop();
+            b.hard_drop();
+        }
+        assert_eq!(a.screen.rows, b.screen.rows);
+        assert_eq!(a.scores, b.scores);
+        assert_eq!(a.next_shapes(), b.next_shapes());
+    }
+
+    #[test]
+    fn hard_drop_scores_and_locks() {
+        let mut game = game();
+        let cells = game.drop_distance();
+        game.hard_drop();
+        assert_eq!(game.scores, 2 * cells as usize);
+        let filled: u32 = game.screen.rows.iter().map(|row| row.count_ones()).sum();
+        assert_eq!(filled, 4);
+    }
+
+    #[test]
+    fn tetris_then_back_to_back_tetris() {
+        let mut game = game();
+        // Not a perfect clear
+        fill(&mut game, 39, &[9]);
+        for round in 0..2 {
+            for y in 35..39 {
+                fill(&mut game, y, &[0]);
+            }
+            place(&mut game, Shapes::Ishape, 1, -2, 29);
+            game.hard_drop();
+            let clear = game.last_clear.unwrap();
+            assert_eq!(clear.lines, 4);
+            if round == 0 {
+                assert_eq!(clear.points, 800);
+            } else {
+                // 800 * 1.5 and combo 1
+                assert!(clear.back_to_back);
+                assert_eq!(clear.points, 1200 + 50);
+            }
+        }
+        assert!(game.screen.rows[..39].iter().all(|&row| row == 0));
+    }
+
+    #[test]
+    fn t_spin_double() {
+        let mut game = game();
+        fill(&mut game, 39, &[4]);
+        fill(&mut game, 38, &[3, 4, 5]);
+        game.screen.set(5, 37, Shapes::Oshape);
+        place(&mut game, Shapes::Tshape, 1, 3, 37);
+        game.rotate(1);
+        game.hard_drop();
+        let clear = game.last_clear.unwrap();
+        assert_eq!(
+            (clear.lines, clear.spin, clear.points),
+            (2, Spin::Full, 1200)
+        );
+    }
+
+    #[test]
+    fn no_spin_without_rotation() {
+        let mut game = game();
+        fill(&mut game, 39, &[4]);
+        fill(&mut game, 38, &[3, 4, 5]);
+        game.screen.set(5, 37, Shapes::Oshape);
+        place(&mut game, Shapes::Tshape, 2, 3, 37);
+        game.hard_drop();
+        let clear = game.last_clear.unwrap();
+        assert_eq!((clear.lines, clear.spin, clear.points), (2, Spin::No, 300));
+    }
+
+    #[test]
+    fn perfect_clear() {
+        let mut game = game();
+        fill(&mut game, 39, &[3, 4, 5, 6]);
+        place(&mut game, Shapes::Ishape, 0, 3, 30);
+        game.hard_drop();
+        let clear = game.last_clear.unwrap();
+        assert!(clear.perfect);
+        assert_eq!(clear.points, 100 + 800);
+    }
+
+    #[test]
+    fn lock_delay_on_the_ground() {
+        let mut game = game();
+        while game.move_down() {}
+        for _ in 1..game.rules.lock_delay {
+            game.tick();
+        }
+        assert!(game.screen.is_empty());
+        game.tick();
+        assert!(!game.screen.is_empty());
+    }
+
+    #[test]
+    fn lock_resets_run_out_on_the_floor() {
+        let mut game = game();
+        place(&mut game, Shapes::Tshape, 0, 3, 30);
+        while game.move_down() {}
+        // Floor kicks up and falls back, to both sides not to go to a wall
+        for round in 0..8 {
+            let rotate = if round % 2 == 0 { 1 } else { -1 };
+            game.rotate(rotate);
+            game.rotate(-rotate);
+            assert!(game.move_down());
+        }
+        for _ in 0..20 {
+            game.tick();
+        }
+        assert!(game.screen.is_empty());
+
+        // No resets left: falling back doesn't restart the lock delay
+        game.rotate(1);
+        game.rotate(-1);
+        assert!(game.move_down());
+        for _ in 20..game.rules.lock_delay {
+            game.tick();
+        }
+        assert!(!game.screen.is_empty());
+    }
+
+    #[test]
+    fn hold_once_per_drop() {
+        let mut game = game();
+        let first = game.current_shape.shape;
+        let next = game.next_shapes()[0];
+        game.hold();
+        assert_eq!(game.hold_shape, Some(first));
+        assert_eq!(game.current_shape.shape, next);
+        game.hold();
+        assert_eq!(game.current_shape.shape, next);
+        game.hard_drop();
+        game.hold();
+        assert_eq!(game.current_shape.shape, first);
+    }
+
+    #[test]
+    fn das_then_arr() {
+        let mut game = game();
+        let x = game.current_shape.x;
+        game.press(Key::Right);
+        assert_eq!(game.current_shape.x, x + 1);
+        for _ in 1..game.handling.das {
+            game.tick();
+        }
+        assert_eq!(game.current_shape.x, x + 1);
+        game.tick();
+        assert_eq!(game.current_shape.x, x + 2);
+        for _ in 0..game.handling.arr {
+            game.tick();
+        }
+        assert_eq!(game.current_shape.x, x + 3);
+        game.release(Key::Right);
+        for _ in 0..game.handling.das {
+            game.tick();
+        }
+        assert_eq!(game.current_shape.x, x + 3);
+    }
+
+    #[test]
+    fn das_then_instant_arr() {
+        let mut game = game();
+        game.handling.arr = 0;
+        game.press(Key::Left);
+        for _ in 0..game.handling.das {
+            game.tick();
+        }
+        assert_eq!(game.current_shape.x, 0);
+    }
+
+    #[test]
+    fn held_late_without_move() {
+        let mut game = game();
+        let x = game.current_shape.x;
+        // Two taps, then the third byte of the autorepeat:
+        // held since the first tap
+        for _ in 0..2 {
+            game.press(Key::Right);
+            game.release(Key::Right);
+            for _ in 0..2 {
+                game.tick();
+            }
+        }
+        game.press_held(Key::Right, 4);
+        assert_eq!(game.current_shape.x, x + 2);
+        for _ in 4..game.handling.das - 1 {
+            game.tick();
+        }
+        assert_eq!(game.current_shape.x, x + 2);
+        game.tick();
+        assert_eq!(game.current_shape.x, x + 3);
+
+        // Long held: DAS is charged
+        game.release(Key::Right);
+        game.press_held(Key::Right, 100);
+        game.tick();
+        assert_eq!(game.current_shape.x, x + 4);
+    }
+
+    #[test]
+    fn lock_out_above_the_field() {
+        let mut game = game();
+        while !game.over {
+            game.hard_drop();
+        }
+        let hidden = game.screen.hidden;
+        assert!(game.screen.rows[..hidden].iter().any(|&row| row != 0));
+    }
+}
@@ -0,0 +1,78 @@
+// Gravity is in rows per frame (G) with 16 fraction bits:
+// ROW is 1G, 1/60G is one row per second at 60 frames per second
+pub const ROW: u32 = 1 << 16;
+// Shape falls to the ground in the same frame
+pub const MAX: u32 = 20 * ROW;
+
+pub const FRAMES_PER_SECOND: u32 = 60;
+
+// Guideline: (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row,
+// levels 1..=19, 20G from level 20 (rounded up, level 1 falls in 60 frames)
+const GUIDELINE: [u32; 19] = [
+    1093, 1378, 1769, 2311, 3076, 4169, 5759, 8107, 11635, 17027, 25416, 38709, 60169, 95484,
+    154743, 256187, 433425, 749597, MAX,
+];
+
+// NES: frames per row for levels 0..=29, 1 frame per row from level 29
+// (gravity is rounded up, so a row takes just these frames)
+const CLASSIC: [u32; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+// Gravity of levels
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GravityCurve {
+    Guideline,
+    Classic,
+    // Same gravity on every level
+    Constant(u32),
+}
+
+impl GravityCurve {
+    // Gravity curve by name (for options)
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"guideline" => Some(Self::Guideline),
+            b"classic" => Some(Self::Classic),
+            b"20g" => Some(Self::Constant(MAX)),
+            _ => None,
+        }
+    }
+
+    pub fn gravity(self, level: usize) -> u32 {
+        match self {
+            Self::Guideline => match level {
+                0 => GUIDELINE[0],
+                1..=19 => GUIDELINE[level - 1],
+                _ => MAX,
+            },
+            Self::Classic => ROW.div_ceil(CLASSIC.get(level).copied().unwrap_or(1)),
+            Self::Constant(gravity) => gravity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frames until the shape falls one row
+    fn frames(curve: GravityCurve, level: usize) -> u32 {
+        let gravity = curve.gravity(level);
+        (1..).find(|frames| frames * gravity >= ROW).unwrap()
+    }
+
+    #[test]
+    fn frames_per_row() {
+        assert_eq!(frames(GravityCurve::Guideline, 1), 60);
+        assert_eq!(frames(GravityCurve::Guideline, 2), 48);
+        assert_eq!(frames(GravityCurve::Guideline, 5), 22);
+        assert_eq!(frames(GravityCurve::Guideline, 9), 6);
+        assert_eq!(frames(GravityCurve::Guideline, 10), 4);
+        assert_eq!(frames(GravityCurve::Guideline, 14), 1);
+        assert_eq!(frames(GravityCurve::Classic, 0), 48);
+        assert_eq!(frames(GravityCurve::Classic, 18), 3);
+        assert_eq!(frames(GravityCurve::Classic, 29), 1);
+        assert_eq!(GravityCurve::Guideline.gravity(20), MAX);
+    }
+}
@@ -0,0 +1,68 @@
+// Keys of the game (frontends map their input to them)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Hold,
+}
+
+// How held keys repeat (player settings, not rules)
+// (times in frames)
+#[derive(Clone, Copy, Debug)]
+pub struct Handling {
+    // Delayed auto shift: hold time before the shape starts to slide
+    pub das: u32,
+    // Auto repeat rate: time between slides (0 goes to the wall at once)
+    pub arr: u32,
+    pub das_charge: DasCharge,
+    // Time between rows of held soft drop
+    pub soft_drop: u32,
+}
+
+// What happens to a charged DAS when a new shape comes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DasCharge {
+    // Stays charged, the new shape slides at once
+    Keep,
+    // Charges again from zero
+    Reset,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self {
+            das: 10,
+            arr: 2,
+            das_charge: DasCharge::Keep,
+            soft_drop: 2,
+        }
+    }
+}
+
+impl DasCharge {
+    // DAS charge by name (for options)
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"keep" => Some(Self::Keep),
+            b"reset" => Some(Self::Reset),
+            _ => None,
+        }
+    }
+}
+
+// Held keys and their timers
+#[derive(Clone, Default)]
+pub struct Held {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+    // Slide side of the last pressed held key (-1, 0, 1)
+    pub side: i32,
+    pub das_frames: u32,
+    pub arr_frames: u32,
+    pub soft_drop_frames: u32,
+}
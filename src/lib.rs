@@ -0,0 +1,37 @@
+#![cfg_attr(not(test), no_std)]
+
+// Tetris engine
+// (pure game rules, no io: frontends draw Game and feed it actions)
+
+pub mod shape;
+pub use crate::shape::{Shape, Shapes};
+
+pub mod row;
+pub use crate::row::Row;
+
+pub mod screen;
+pub use crate::screen::{LineGravity, Screen};
+
+pub mod rng;
+pub use crate::rng::Rng;
+
+pub mod rotation;
+pub use crate::rotation::RotationSystem;
+
+pub mod randomizer;
+pub use crate::randomizer::Randomizer;
+
+pub mod gravity;
+pub use crate::gravity::GravityCurve;
+
+pub mod score;
+pub use crate::score::{Clear, Spin};
+
+pub mod rules;
+pub use crate::rules::{LockReset, Ruleset};
+
+pub mod input;
+pub use crate::input::{DasCharge, Handling, Key};
+
+pub mod game;
+pub use crate::game::Game;
@@ -1,205 +1,241 @@
-#![allow(invalid_value)]
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
-// Import Shapes structs and Screen struct
-mod shape;
-use crate::shape::Shape;
+// Import game engine
+use tetris::{Clear, Game, Shape, Spin};
 
-mod screen;
-use crate::screen::Screen;
+// Frontend parts
+mod tui;
+use crate::tui::clock::{self, FRAME_NS};
+use crate::tui::input::Input;
+use crate::tui::options::Options;
+use crate::tui::render::Renderer;
+use crate::tui::term;
+use crate::tui::theme::Theme;
 
-// For init fd_set and termios var-s
-use core::mem::MaybeUninit;
-
-// For support all system (on android *const u8)
-type Cstr = *const i8;
+use core::fmt::Write;
 
 // Import libc types and a few funcs/macroses
 extern crate libc;
-use libc::{
-    c_char, c_int, c_uint, c_void, fd_set, size_t, termios, time_t, timeval, ECHO, FD_ISSET,
-    FD_SET, FD_ZERO, ICANON, STDIN_FILENO, TCSANOW,
-};
+use libc::{c_char, c_int};
 
 // Link libc funcs
 #[cfg(target_os = "linux")]
 #[link(name = "c")]
-
 extern "C" {
-    fn free(p: *mut c_void);
-    fn malloc(size: size_t) -> *mut c_void;
-    fn time(time: *mut time_t) -> time_t;
-    fn srand(seed: c_uint);
     fn printf(format: *const c_char, ...) -> c_int;
-    fn usleep(secs: c_uint) -> c_int;
-    fn tcgetattr(fd: c_int, termios: *mut termios) -> c_int;
-    fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const termios) -> c_int;
-    fn getchar() -> c_int;
-    fn select(
-        nfds: c_int,
-        readfds: *mut fd_set,
-        writefds: *mut fd_set,
-        errorfds: *mut fd_set,
-        timeout: *mut timeval,
-    ) -> c_int;
-    fn system(s: *const c_char) -> c_int;
 }
 
-// For Box<T> and Vec<T>
-extern crate alloc;
-use core::alloc::{GlobalAlloc, Layout};
+// (under test the harness has its own main)
+#[cfg_attr(not(test), no_mangle)]
+fn main(argc: isize, argv: *const *const u8) -> isize {
+    // Init game
+    let options = Options::parse(argc, argv);
+    term::enter();
+    let mut game = Game::new(10, 20, options.seed, options.rules.clone());
+    game.handling = options.handling;
+    let mut input = Input::new();
+    input.enable_kitty();
+    let mut renderer = Renderer::new(options.depth);
+    renderer.resize(term::size());
 
-#[derive(Default)]
-pub struct Allocator;
-unsafe impl GlobalAlloc for Allocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        malloc(layout.size() as usize) as *mut u8
-    }
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-        free(ptr as *mut c_void);
-    }
-}
+    // Game loop
+    // (60 frames per second, keys are read while waiting the next frame)
+    print(&game, &options, &mut renderer);
+    let mut next_frame = clock::now() + FRAME_NS;
+    while !game.over {
+        if term::resumed() {
+            // Stopped game was paused: no lost frames, no stale held keys
+            input.release_all(&mut game);
+            renderer.invalidate();
+            print(&game, &options, &mut renderer);
+            next_frame = clock::now() + FRAME_NS;
+        }
+        if term::resized() {
+            renderer.resize(term::size());
+            print(&game, &options, &mut renderer);
+        }
+
+        let now = clock::now();
+        if now < next_frame {
+            let wait = ((next_frame - now) / 1000) as i64;
+            if input.read(&mut game, wait) {
+                print(&game, &options, &mut renderer);
+            }
+            continue;
+        }
 
-#[global_allocator]
-static GLOBAL_ALLOCATOR: Allocator = Allocator;
+        let before = moment(&game);
+        input.tick(&mut game);
+        game.tick();
+        if moment(&game) != before {
+            print(&game, &options, &mut renderer);
+        }
 
-#[no_mangle]
-fn main(_argc: isize, _argv: *const *const u8) -> isize {
-    // Init random
-    unsafe {
-        srand(time(core::ptr::null_mut()) as u32);
-    }
-    // Input init
-    let mut oldt;
-    let mut newt;
-    unsafe {
-        oldt = MaybeUninit::<termios>::uninit().assume_init();
-        tcgetattr(STDIN_FILENO, &mut oldt);
-        newt = oldt;
-        newt.c_lflag &= !(ICANON | ECHO);
-        tcsetattr(STDIN_FILENO, TCSANOW, &newt);
+        next_frame += FRAME_NS;
+        // Too late (stopped process), don't run the lost frames
+        if now > next_frame + 10 * FRAME_NS {
+            next_frame = now + FRAME_NS;
+        }
     }
+    input.disable_kitty();
+    term::restore();
+    game_over(game.scores, game.lines, game.seed);
+    0
+}
 
-    // Init game
-    let mut game = Screen::new(10, 20);
-    let mut current_shape = Shape::new(game.rows as i32);
-    let mut next_shape = Shape::new(game.rows as i32);
-    game.put(&current_shape);
-    let (mut new_scores, mut new_lines) = (0, 0);
-    let (mut scores, mut lines) = (0, 0usize);
+// Rows of the vanish zone shown above the field
+const SPAWN_SHOWN: usize = 2;
+
+// Columns of the HUD left of the next queue
+const HUD_WIDTH: usize = 30;
+
+// How long the last clear is shown (frames)
+const NOTICE_FRAMES: u32 = 120;
+
+// What is seen of the game (print again when it changes)
+fn moment(game: &Game) -> (i32, i32, i32, usize, bool, bool) {
+    let shape = &game.current_shape;
+    let notice = game.clear_frames < NOTICE_FRAMES;
+    (
+        shape.x,
+        shape.y,
+        shape.rotate,
+        game.scores,
+        notice,
+        game.over,
+    )
+}
 
-    // Game loop
-    let mut game_loop = true;
-    while game_loop {
-        // Print game
-        game.print(scores, lines, &next_shape);
-
-        // Input
-        // 20 inputs per input time (input time in usleep)
-        for _ in 0..20 {
-            unsafe {
-                usleep(((700_000 - (lines / 10) * 1000) / 60) as u32);
-
-                // Magic
-                let mut tv = timeval {
-                    tv_sec: 0,
-                    tv_usec: 0,
-                };
-                let mut fds = MaybeUninit::<fd_set>::uninit().assume_init();
-                FD_ZERO(&mut fds);
-                FD_SET(STDIN_FILENO, &mut fds);
-                select(
-                    STDIN_FILENO + 1,
-                    &mut fds,
-                    core::ptr::null_mut(),
-                    core::ptr::null_mut(),
-                    &mut tv,
-                );
-                if FD_ISSET(STDIN_FILENO, &fds as *const fd_set) {
-                    // What key did hit
-                    let mut key = getchar();
-                    if key == 27 {
-                        // ANSI Escape sequence "27 91 Key_code"
-                        getchar(); // skip 91
-                        key = getchar();
-                    }
-                    match key {
-                        // Left array
-                        68 => {
-                            current_shape = game.move_side(&mut current_shape, -1);
-                            game.print(scores, lines, &next_shape);
-                        }
-                        // Right array
-                        67 => {
-                            current_shape = game.move_side(&mut current_shape, 1);
-                            game.print(scores, lines, &next_shape);
-                        }
-                        // Up array
-                        65 => {
-                            current_shape = game.rotate(&mut current_shape, 1);
-                            game.print(scores, lines, &next_shape);
-                        }
-                        // Down array
-                        66 => {
-                            current_shape = game.rotate(&mut current_shape, -1);
-                            game.print(scores, lines, &next_shape);
-                        }
-                        // Drop
-                        32 => {
-                            break;
-                        }
-                        _ => (),
-                    }
-                }
+// Draw board with falling shape and the HUD
+fn print(game: &Game, options: &Options, renderer: &mut Renderer) {
+    let screen = &game.screen;
+    let ghost = game.ghost_shape();
+    let theme = &options.theme;
+    renderer.clear();
+
+    // Spawn rows of the vanish zone are shown above the field
+    let top = screen.hidden.saturating_sub(SPAWN_SHOWN);
+    for y in top..screen.height {
+        renderer.text(b"\n");
+        for x in 0..screen.width {
+            let hidden = y < screen.hidden;
+            let (x, y) = (x as i32, y as i32);
+            let kind = if game.current_shape.contains(x, y) {
+                Some(game.current_shape.shape)
+            } else {
+                screen.kind(x as usize, y as usize)
+            };
+            if let Some(kind) = kind {
+                renderer.color(Some(theme.shape(kind))).text(b"[] ");
+            } else if options.ghost && ghost.contains(x, y) {
+                renderer.color(Some(theme.ghost)).text(b"() ");
+            } else if hidden {
+                renderer.text(b"   ");
+            } else {
+                renderer.text(b".. ");
             }
+            renderer.color(None);
         }
+    }
 
-        // Update and print
-        match game.move_down(&mut current_shape) {
-            Err(()) => {
-                game_over(scores, lines);
-                game_loop = false;
-            },
-            Ok(result) => {
-                let result_shape;
-                let current_or_next;
-                (result_shape, current_or_next, new_scores, new_lines) = result;
-
-                // current shape does not change to new shape
-                if !current_or_next {
-                    current_shape = result_shape;
-                } else {
-                    current_shape = next_shape;
-                    next_shape = result_shape;
-                }
-            }
+    // HUD in columns right of the board (cells are 3 wide),
+    // the whole frame fits 80x24
+    let hud = 3 * screen.width + 2;
+    renderer.at(hud, 1);
+    let _ = write!(
+        renderer,
+        "SCORES: {}\nLINES: {}\nLEVEL: {}\nSEED: {}",
+        game.scores, game.lines, game.level, game.seed,
+    );
+    if game.rules.hold {
+        renderer.at(hud, 6).text(b"HOLD:");
+        if let Some(held) = game.hold_shape {
+            print_shape(&Shape::new(0, held, game.rules.rotation), theme, renderer);
+        }
+    }
+    renderer.at(hud, 9);
+    if game.combo > 0 {
+        let _ = write!(renderer, "\nCOMBO: {}", game.combo);
+    }
+    if game.back_to_back {
+        renderer.text(b"\nB2B");
+    }
+    if let Some(clear) = game.last_clear {
+        if game.clear_frames < NOTICE_FRAMES {
+            print_clear(&clear, renderer);
         }
-        scores += new_scores;
-        lines += new_lines;
     }
 
-    unsafe {
-        tcsetattr(STDIN_FILENO, TCSANOW, &oldt);
+    renderer.at(hud + HUD_WIDTH, 1).text(b"NEXT:");
+    for &next in game.next_shapes() {
+        print_shape(&Shape::new(0, next, game.rules.rotation), theme, renderer);
     }
-    0
+    renderer.flush();
+}
+
+// Notification of the last clear, like "B2B T-SPIN DOUBLE"
+fn print_clear(clear: &Clear, renderer: &mut Renderer) {
+    let lines = ["", "SINGLE", "DOUBLE", "TRIPLE", "TETRIS"];
+    let spin = match clear.spin {
+        Spin::No => "",
+        Spin::Mini => "T-SPIN MINI ",
+        Spin::Full => "T-SPIN ",
+    };
+    let b2b = if clear.back_to_back { "B2B " } else { "" };
+    let _ = write!(
+        renderer,
+        "\n{}{}{} +{}",
+        b2b,
+        spin,
+        lines[clear.lines.min(4)],
+        clear.points,
+    );
+    if clear.perfect {
+        renderer.text(b"\nPERFECT CLEAR");
+    }
+}
+
+// Draw only rows with cells (compact for the next queue)
+fn print_shape(shape: &Shape, theme: &Theme, renderer: &mut Renderer) {
+    for row in shape.canvas.chunks(shape.dx) {
+        if !row.contains(&true) {
+            continue;
+        }
+        renderer.text(b"\n");
+        for &cell in row {
+            if cell {
+                renderer.color(Some(theme.shape(shape.shape))).text(b"## ");
+                renderer.color(None);
+            } else {
+                renderer.text(b"   ");
+            }
+        }
+    }
+    renderer.text(b"\n");
 }
 
-fn game_over(scores: usize, lines: usize) {
+fn game_over(scores: usize, lines: usize, seed: u64) {
+    // Printed on the main screen, it stays after the game
     unsafe {
-        system("clear\0".as_ptr() as Cstr);
-        printf("\n _____   ___  ___  ___ _____   _____  _   _ ___________\n\0".as_ptr() as Cstr);
-        printf("|  __ \\ / _ \\ |  \\/  ||  ___| |  _  || | | |  ___| ___ \\ \n\0".as_ptr() as Cstr);
-        printf("| |  \\// /_\\ \\| .  . || |__   | | | || | | | |__ | |_/ /\n\0".as_ptr() as Cstr);
-        printf("| | __ |  _  || |\\/| ||  __|  | | | || | | |  __||    / \n\0".as_ptr() as Cstr);
-        printf("| |_\\ \\| | | || |  | || |___  \\ \\_/ /\\ \\_/ / |___| |\\ \\ \n\0".as_ptr() as Cstr);
-        printf("\\____/\\_| |_/\\_|  |_/\\____/   \\___/  \\___/\\____/\\_| \\_|\n\0".as_ptr() as Cstr);
-        printf("\n\nYOUR SCORES: %d; YOUR LINES: %d;\n\0".as_ptr() as Cstr, scores, lines);
+        printf(c"\n _____   ___  ___  ___ _____   _____  _   _ ___________\n".as_ptr());
+        printf(c"|  __ \\ / _ \\ |  \\/  ||  ___| |  _  || | | |  ___| ___ \\ \n".as_ptr());
+        printf(c"| |  \\// /_\\ \\| .  . || |__   | | | || | | | |__ | |_/ /\n".as_ptr());
+        printf(c"| | __ |  _  || |\\/| ||  __|  | | | || | | |  __||    / \n".as_ptr());
+        printf(c"| |_\\ \\| | | || |  | || |___  \\ \\_/ /\\ \\_/ / |___| |\\ \\ \n".as_ptr());
+        printf(c"\\____/\\_| |_/\\_|  |_/\\____/   \\___/  \\___/\\____/\\_| \\_|\n".as_ptr());
+        printf(
+            c"\n\nYOUR SCORES: %d; YOUR LINES: %d;\n".as_ptr(),
+            scores,
+            lines,
+        );
+        printf(c"REPLAY WITH SEED: %llu\n".as_ptr(), seed);
     }
 }
 
 // For rust compiler
+#[cfg(not(test))]
 #[panic_handler]
-fn panic(_info: &core::panic::PanicInfo<'_>) -> ! {
-    loop {}
+fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    term::panic(info)
 }
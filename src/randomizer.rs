@@ -0,0 +1,174 @@
+// Import Shapes structs
+use crate::shape::{Shapes, SHAPES};
+use crate::Rng;
+
+// How the next shape is chosen
+#[derive(Clone)]
+pub enum Randomizer {
+    // Every shape with the same chance, any drought possible
+    Random,
+    // Shuffled bag of `size` shapes (every shape size / 7 times),
+    // new bag when the old one is empty
+    Bag {
+        bag: [Shapes; 14],
+        size: usize,
+        left: usize,
+    },
+    // TGM: reroll up to `rolls` times while the shape
+    // is one of the 4 last shapes
+    History {
+        history: [Shapes; 4],
+        rolls: u32,
+        first: bool,
+    },
+}
+
+impl Randomizer {
+    pub fn random() -> Self {
+        Self::Random
+    }
+
+    pub fn bag7() -> Self {
+        Self::bag(7)
+    }
+
+    pub fn bag14() -> Self {
+        Self::bag(14)
+    }
+
+    fn bag(size: usize) -> Self {
+        Self::Bag {
+            bag: [Shapes::Tshape; 14],
+            size,
+            left: 0,
+        }
+    }
+
+    // TGM1: history starts as Z Z Z Z, 4 rolls
+    pub fn tgm() -> Self {
+        Self::History {
+            history: [Shapes::Zshape; 4],
+            rolls: 4,
+            first: true,
+        }
+    }
+
+    // TGM2: history starts as Z S S Z, 6 rolls
+    pub fn tgm2() -> Self {
+        Self::History {
+            history: [
+                Shapes::Zshape,
+                Shapes::Sshape,
+                Shapes::Sshape,
+                Shapes::Zshape,
+            ],
+            rolls: 6,
+            first: true,
+        }
+    }
+
+    // Randomizer by name (for options)
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"random" => Some(Self::random()),
+            b"bag7" => Some(Self::bag7()),
+            b"bag14" => Some(Self::bag14()),
+            b"tgm" => Some(Self::tgm()),
+            b"tgm2" => Some(Self::tgm2()),
+            _ => None,
+        }
+    }
+
+    pub fn next(&mut self, rng: &mut Rng) -> Shapes {
+        match self {
+            Self::Random => SHAPES[rng.below(SHAPES.len() as u32) as usize],
+            Self::Bag { bag, size, left } => {
+                if *left == 0 {
+                    // Fill and shuffle (Fisher-Yates)
+                    for (i, shape) in bag[..*size].iter_mut().enumerate() {
+                        *shape = SHAPES[i % SHAPES.len()];
+                    }
+                    for i in (1..*size).rev() {
+                        let j = rng.below(i as u32 + 1) as usize;
+                        bag.swap(i, j);
+                    }
+                    *left = *size;
+                }
+                *left -= 1;
+                bag[*left]
+            }
+            Self::History {
+                history,
+                rolls,
+                first,
+            } => {
+                let mut shape;
+                if *first {
+                    // First shape is never S, Z or O
+                    *first = false;
+                    let starts = [
+                        Shapes::Ishape,
+                        Shapes::Jshape,
+                        Shapes::Lshape,
+                        Shapes::Tshape,
+                    ];
+                    shape = starts[rng.below(starts.len() as u32) as usize];
+                } else {
+                    shape = SHAPES[rng.below(SHAPES.len() as u32) as usize];
+                    for _ in 1..*rolls {
+                        if !history.contains(&shape) {
+                            break;
+                        }
+                        shape = SHAPES[rng.below(SHAPES.len() as u32) as usize];
+                    }
+                }
+                history.rotate_right(1);
+                history[0] = shape;
+                shape
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Count of every shape in draws
+    fn counts(randomizer: &mut Randomizer, rng: &mut Rng, draws: usize) -> [usize; 7] {
+        let mut counts = [0; 7];
+        for _ in 0..draws {
+            counts[randomizer.next(rng) as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn bag7_deals_every_shape_once() {
+        let (mut bag, mut rng) = (Randomizer::bag7(), Rng::new(1));
+        for _ in 0..100 {
+            assert_eq!(counts(&mut bag, &mut rng, 7), [1; 7]);
+        }
+    }
+
+    #[test]
+    fn bag14_deals_every_shape_twice() {
+        let (mut bag, mut rng) = (Randomizer::bag14(), Rng::new(2));
+        for _ in 0..100 {
+            assert_eq!(counts(&mut bag, &mut rng, 14), [2; 7]);
+        }
+    }
+
+    #[test]
+    fn tgm_starts_without_s_z_o() {
+        for seed in 0..1000 {
+            for mut tgm in [Randomizer::tgm(), Randomizer::tgm2()] {
+                let first = tgm.next(&mut Rng::new(seed));
+                assert!(!matches!(
+                    first,
+                    Shapes::Sshape | Shapes::Zshape | Shapes::Oshape
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,600 @@
+// Import Shapes structs and Screen struct
+use crate::{Row, Screen, Shape, Shapes};
+
+// Rotation system: shape of every rotation state and the kicks
+// States are 0 spawn, 1 right, 2 reverse, 3 left; +1 is clockwise
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RotationSystem {
+    // Super Rotation System (guideline), with wall and floor kicks
+    Srs,
+    // Arika Rotation System (TGM), bottom aligned, kicks 1 right / 1 left
+    Ars,
+    // Nintendo classic, right-handed, no kicks
+    Nes,
+}
+
+// Cells of rotation tables
+const X: bool = true;
+const E: bool = false;
+
+// Canvases of the 4 rotation states with masks of their rows
+// (bit i is the column i), made once at compile time
+struct States {
+    canvas: [&'static [bool]; 4],
+    rows: [[u8; 4]; 4],
+}
+
+impl States {
+    const fn new(canvas: [&'static [bool]; 4]) -> Self {
+        let mut rows = [[0; 4]; 4];
+        let mut state = 0;
+        while state < 4 {
+            let cells = canvas[state];
+            let dx = if cells.len() == 4 {
+                2
+            } else if cells.len() == 9 {
+                3
+            } else {
+                4
+            };
+            let mut cell = 0;
+            while cell < cells.len() {
+                if cells[cell] {
+                    rows[state][cell / dx] |= 1 << (cell % dx);
+                }
+                cell += 1;
+            }
+            state += 1;
+        }
+        Self { canvas, rows }
+    }
+}
+
+#[rustfmt::skip]
+const SRS_T: States = States::new([
+    &[E, X, E,
+      X, X, X,
+      E, E, E],
+    &[E, X, E,
+      E, X, X,
+      E, X, E],
+    &[E, E, E,
+      X, X, X,
+      E, X, E],
+    &[E, X, E,
+      X, X, E,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const SRS_I: States = States::new([
+    &[E, E, E, E,
+      X, X, X, X,
+      E, E, E, E,
+      E, E, E, E],
+    &[E, E, X, E,
+      E, E, X, E,
+      E, E, X, E,
+      E, E, X, E],
+    &[E, E, E, E,
+      E, E, E, E,
+      X, X, X, X,
+      E, E, E, E],
+    &[E, X, E, E,
+      E, X, E, E,
+      E, X, E, E,
+      E, X, E, E],
+]);
+
+#[rustfmt::skip]
+const O: States = States::new([
+    &[X, X,
+      X, X],
+    &[X, X,
+      X, X],
+    &[X, X,
+      X, X],
+    &[X, X,
+      X, X],
+]);
+
+#[rustfmt::skip]
+const SRS_S: States = States::new([
+    &[E, X, X,
+      X, X, E,
+      E, E, E],
+    &[E, X, E,
+      E, X, X,
+      E, E, X],
+    &[E, E, E,
+      E, X, X,
+      X, X, E],
+    &[X, E, E,
+      X, X, E,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const SRS_Z: States = States::new([
+    &[X, X, E,
+      E, X, X,
+      E, E, E],
+    &[E, E, X,
+      E, X, X,
+      E, X, E],
+    &[E, E, E,
+      X, X, E,
+      E, X, X],
+    &[E, X, E,
+      X, X, E,
+      X, E, E],
+]);
+
+#[rustfmt::skip]
+const SRS_J: States = States::new([
+    &[X, E, E,
+      X, X, X,
+      E, E, E],
+    &[E, X, X,
+      E, X, E,
+      E, X, E],
+    &[E, E, E,
+      X, X, X,
+      E, E, X],
+    &[E, X, E,
+      E, X, E,
+      X, X, E],
+]);
+
+#[rustfmt::skip]
+const SRS_L: States = States::new([
+    &[E, E, X,
+      X, X, X,
+      E, E, E],
+    &[E, X, E,
+      E, X, E,
+      E, X, X],
+    &[E, E, E,
+      X, X, X,
+      X, E, E],
+    &[X, X, E,
+      E, X, E,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const ARS_T: States = States::new([
+    &[E, E, E,
+      X, X, X,
+      E, X, E],
+    &[E, X, E,
+      X, X, E,
+      E, X, E],
+    &[E, E, E,
+      E, X, E,
+      X, X, X],
+    &[E, X, E,
+      E, X, X,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const ARS_I: States = States::new([
+    &[E, E, E, E,
+      X, X, X, X,
+      E, E, E, E,
+      E, E, E, E],
+    &[E, E, X, E,
+      E, E, X, E,
+      E, E, X, E,
+      E, E, X, E],
+    &[E, E, E, E,
+      X, X, X, X,
+      E, E, E, E,
+      E, E, E, E],
+    &[E, E, X, E,
+      E, E, X, E,
+      E, E, X, E,
+      E, E, X, E],
+]);
+
+#[rustfmt::skip]
+const ARS_S: States = States::new([
+    &[E, E, E,
+      E, X, X,
+      X, X, E],
+    &[X, E, E,
+      X, X, E,
+      E, X, E],
+    &[E, E, E,
+      E, X, X,
+      X, X, E],
+    &[X, E, E,
+      X, X, E,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const ARS_Z: States = States::new([
+    &[E, E, E,
+      X, X, E,
+      E, X, X],
+    &[E, E, X,
+      E, X, X,
+      E, X, E],
+    &[E, E, E,
+      X, X, E,
+      E, X, X],
+    &[E, E, X,
+      E, X, X,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const ARS_J: States = States::new([
+    &[E, E, E,
+      X, X, X,
+      E, E, X],
+    &[E, X, E,
+      E, X, E,
+      X, X, E],
+    &[E, E, E,
+      X, E, E,
+      X, X, X],
+    &[E, X, X,
+      E, X, E,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const ARS_L: States = States::new([
+    &[E, E, E,
+      X, X, X,
+      X, E, E],
+    &[X, X, E,
+      E, X, E,
+      E, X, E],
+    &[E, E, E,
+      E, E, X,
+      X, X, X],
+    &[E, X, E,
+      E, X, E,
+      E, X, X],
+]);
+
+#[rustfmt::skip]
+const NES_T: States = States::new([
+    &[E, E, E,
+      X, X, X,
+      E, X, E],
+    &[E, X, E,
+      X, X, E,
+      E, X, E],
+    &[E, X, E,
+      X, X, X,
+      E, E, E],
+    &[E, X, E,
+      E, X, X,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const NES_I: States = States::new([
+    &[E, E, E, E,
+      E, E, E, E,
+      X, X, X, X,
+      E, E, E, E],
+    &[E, E, X, E,
+      E, E, X, E,
+      E, E, X, E,
+      E, E, X, E],
+    &[E, E, E, E,
+      E, E, E, E,
+      X, X, X, X,
+      E, E, E, E],
+    &[E, E, X, E,
+      E, E, X, E,
+      E, E, X, E,
+      E, E, X, E],
+]);
+
+#[rustfmt::skip]
+const NES_S: States = States::new([
+    &[E, E, E,
+      E, X, X,
+      X, X, E],
+    &[E, X, E,
+      E, X, X,
+      E, E, X],
+    &[E, E, E,
+      E, X, X,
+      X, X, E],
+    &[E, X, E,
+      E, X, X,
+      E, E, X],
+]);
+
+#[rustfmt::skip]
+const NES_Z: States = States::new([
+    &[E, E, E,
+      X, X, E,
+      E, X, X],
+    &[E, E, X,
+      E, X, X,
+      E, X, E],
+    &[E, E, E,
+      X, X, E,
+      E, X, X],
+    &[E, E, X,
+      E, X, X,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const NES_J: States = States::new([
+    &[E, E, E,
+      X, X, X,
+      E, E, X],
+    &[E, X, E,
+      E, X, E,
+      X, X, E],
+    &[X, E, E,
+      X, X, X,
+      E, E, E],
+    &[E, X, X,
+      E, X, E,
+      E, X, E],
+]);
+
+#[rustfmt::skip]
+const NES_L: States = States::new([
+    &[E, E, E,
+      X, X, X,
+      X, E, E],
+    &[X, X, E,
+      E, X, E,
+      E, X, E],
+    &[E, E, X,
+      X, X, X,
+      E, E, E],
+    &[E, X, E,
+      E, X, E,
+      E, X, X],
+]);
+
+// Super Rotation System wall kicks
+// Offsets are (x right, y up) as in the guideline tables,
+// rotation tries them in order and takes the first one that fits.
+// Rows: 0->R, R->0, R->2, 2->R, 2->L, L->2, L->0, 0->L
+const JLSTZ_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+];
+
+const I_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+];
+
+// ARS: basic rotation, then 1 right, then 1 left
+const ARS_KICKS: [(i32, i32); 3] = [(0, 0), (1, 0), (-1, 0)];
+
+const NO_KICKS: [(i32, i32); 1] = [(0, 0)];
+
+impl RotationSystem {
+    // Rotation system by name (for options)
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"srs" => Some(Self::Srs),
+            b"ars" => Some(Self::Ars),
+            b"nes" => Some(Self::Nes),
+            _ => None,
+        }
+    }
+
+    fn states(self, shape: Shapes) -> &'static States {
+        match (self, shape) {
+            (_, Shapes::Oshape) => &O,
+            (Self::Srs, Shapes::Tshape) => &SRS_T,
+            (Self::Srs, Shapes::Ishape) => &SRS_I,
+            (Self::Srs, Shapes::Sshape) => &SRS_S,
+            (Self::Srs, Shapes::Zshape) => &SRS_Z,
+            (Self::Srs, Shapes::Jshape) => &SRS_J,
+            (Self::Srs, Shapes::Lshape) => &SRS_L,
+            (Self::Ars, Shapes::Tshape) => &ARS_T,
+            (Self::Ars, Shapes::Ishape) => &ARS_I,
+            (Self::Ars, Shapes::Sshape) => &ARS_S,
+            (Self::Ars, Shapes::Zshape) => &ARS_Z,
+            (Self::Ars, Shapes::Jshape) => &ARS_J,
+            (Self::Ars, Shapes::Lshape) => &ARS_L,
+            (Self::Nes, Shapes::Tshape) => &NES_T,
+            (Self::Nes, Shapes::Ishape) => &NES_I,
+            (Self::Nes, Shapes::Sshape) => &NES_S,
+            (Self::Nes, Shapes::Zshape) => &NES_Z,
+            (Self::Nes, Shapes::Jshape) => &NES_J,
+            (Self::Nes, Shapes::Lshape) => &NES_L,
+        }
+    }
+
+    // Canvas of shape in rotation state
+    // (square dx * dx, row by row)
+    pub fn canvas(self, shape: Shapes, rotate: i32) -> &'static [bool] {
+        self.states(shape).canvas[rotate.rem_euclid(4) as usize]
+    }
+
+    // Masks of canvas rows of shape in rotation state
+    pub fn rows(self, shape: Shapes, rotate: i32) -> &'static [u8; 4] {
+        &self.states(shape).rows[rotate.rem_euclid(4) as usize]
+    }
+
+    // Kicks for rotation from state `from` by `rotate` (1 clockwise, -1 counter)
+    // (x right, y up)
+    fn kicks(self, shape: Shapes, from: i32, rotate: i32) -> &'static [(i32, i32)] {
+        let from = from.rem_euclid(4) as usize;
+        match (self, shape, rotate) {
+            (_, Shapes::Oshape, _) => &NO_KICKS,
+            (Self::Srs, Shapes::Ishape, 1) => &I_KICKS[2 * from],
+            (Self::Srs, Shapes::Ishape, -1) => &I_KICKS[(2 * from + 7) % 8],
+            (Self::Srs, _, 1) => &JLSTZ_KICKS[2 * from],
+            (Self::Srs, _, -1) => &JLSTZ_KICKS[(2 * from + 7) % 8],
+            (Self::Ars, Shapes::Ishape, _) => &NO_KICKS,
+            (Self::Ars, _, _) => &ARS_KICKS,
+            _ => &NO_KICKS,
+        }
+    }
+
+    // Rotate shape on screen with index of the used kick, None if no kick fits
+    pub fn rotate<R: Row>(
+        self,
+        screen: &Screen<R>,
+        shape: &Shape,
+        rotate: i32,
+    ) -> Option<(Shape, usize)> {
+        let rotated = shape.rotate(rotate, self);
+        if self == Self::Ars
+            && matches!(
+                shape.shape,
+                Shapes::Tshape | Shapes::Jshape | Shapes::Lshape
+            )
+            && center_column_blocked(screen, &rotated)
+        {
+            return None;
+        }
+
+        for (kick, &(x, y)) in self
+            .kicks(shape.shape, shape.rotate, rotate)
+            .iter()
+            .enumerate()
+        {
+            let mut kicked = rotated;
+            kicked.x += x;
+            kicked.y -= y;
+            if screen.updatable(&kicked).is_ok() {
+                return Some((kicked, kick));
+            }
+        }
+        None
+    }
+}
+
+// ARS center column rule for L, J, T: the first blocked cell of the
+// rotated shape (reading order) in the middle column forbids the kick
+fn center_column_blocked<R: Row>(screen: &Screen<R>, shape: &Shape) -> bool {
+    for j in 0..shape.dy {
+        for i in 0..shape.dx {
+            if shape.canvas[i + j * shape.dx]
+                && screen.blocked(shape.x + i as i32, shape.y + j as i32)
+            {
+                return i == 1;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Screen {
+        Screen::new(10, 40, 20)
+    }
+
+    // Shape in rotation state at (x; y)
+    fn shape(shape: Shapes, rotate: i32, x: i32, y: i32) -> Shape {
+        let mut shape =
+            Shape::new(10, shape, RotationSystem::Srs).rotate(rotate, RotationSystem::Srs);
+        shape.x = x;
+        shape.y = y;
+        shape
+    }
+
+    #[test]
+    fn row_masks_match_canvas() {
+        for system in [
+            RotationSystem::Srs,
+            RotationSystem::Ars,
+            RotationSystem::Nes,
+        ] {
+            for kind in crate::shape::SHAPES {
+                for rotate in 0..4 {
+                    let shape = Shape::new(10, kind, system).rotate(rotate, system);
+                    for j in 0..shape.dy {
+                        let mask = (0..shape.dx)
+                            .filter(|&i| shape.canvas[i + j * shape.dx])
+                            .fold(0, |mask, i| mask | 1 << i);
+                        assert_eq!(shape.row(j), mask, "{:?} {:?} {}", system, kind, rotate);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn counter_clockwise_undoes_clockwise() {
+        for kind in [Shapes::Tshape, Shapes::Ishape] {
+            for from in 0..4 {
+                let cw = RotationSystem::Srs.kicks(kind, (from + 3) % 4, 1);
+                let ccw = RotationSystem::Srs.kicks(kind, from, -1);
+                for (&(x, y), &(back_x, back_y)) in cw.iter().zip(ccw) {
+                    assert_eq!((back_x, back_y), (-x, -y), "{:?} from {}", kind, from);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wall_kick() {
+        // R -> 2 at the left wall: 1 right
+        let t = shape(Shapes::Tshape, 1, -1, 10);
+        let (rotated, kick) = RotationSystem::Srs.rotate(&screen(), &t, 1).unwrap();
+        assert_eq!((kick, rotated.x, rotated.y, rotated.rotate), (1, 0, 10, 2));
+    }
+
+    #[test]
+    fn floor_kick() {
+        // 0 -> R on the floor: 1 left and 1 up
+        let t = shape(Shapes::Tshape, 0, 4, 38);
+        let (rotated, kick) = RotationSystem::Srs.rotate(&screen(), &t, 1).unwrap();
+        assert_eq!((kick, rotated.x, rotated.y, rotated.rotate), (2, 3, 37, 1));
+    }
+
+    #[test]
+    fn i_kicks() {
+        // R -> 2 at the left wall: 2 right
+        let i = shape(Shapes::Ishape, 1, -2, 10);
+        let (rotated, kick) = RotationSystem::Srs.rotate(&screen(), &i, 1).unwrap();
+        assert_eq!((kick, rotated.x, rotated.rotate), (2, 0, 2));
+
+        // R -> 0 at the left wall: 2 right
+        let (rotated, kick) = RotationSystem::Srs.rotate(&screen(), &i, -1).unwrap();
+        assert_eq!((kick, rotated.x, rotated.rotate), (1, 0, 0));
+    }
+
+    #[test]
+    fn no_kick_fits() {
+        let mut screen = screen();
+        // Standing I in a 1 wide well can't lie down
+        for y in 30..40 {
+            for x in [3, 5] {
+                screen.set(x, y, Shapes::Oshape);
+            }
+        }
+        let i = shape(Shapes::Ishape, 1, 2, 36);
+        assert!(screen.updatable(&i).is_ok());
+        assert!(RotationSystem::Srs.rotate(&screen, &i, 1).is_none());
+        assert!(RotationSystem::Srs.rotate(&screen, &i, -1).is_none());
+    }
+}
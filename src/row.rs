@@ -0,0 +1,31 @@
+// Row of the board as a bitmask
+// (bit x is the column x, so the width is up to BITS)
+use core::ops::{BitAnd, BitOr, Not};
+
+pub trait Row:
+    Copy + Default + Eq + BitAnd<Output = Self> + BitOr<Output = Self> + Not<Output = Self>
+{
+    const BITS: usize;
+
+    // Low bits of u64 (higher ones are dropped)
+    fn from_bits(bits: u64) -> Self;
+    fn bits(self) -> u64;
+}
+
+macro_rules! row {
+    ($($t:ty),*) => {$(
+        impl Row for $t {
+            const BITS: usize = <$t>::BITS as usize;
+
+            fn from_bits(bits: u64) -> Self {
+                bits as $t
+            }
+
+            fn bits(self) -> u64 {
+                self as u64
+            }
+        }
+    )*};
+}
+
+row!(u16, u32, u64);
@@ -0,0 +1,122 @@
+// Import engine parts
+use crate::gravity::GravityCurve;
+use crate::rotation::RotationSystem;
+use crate::{LineGravity, Randomizer};
+
+// Rules of one game
+#[derive(Clone)]
+pub struct Ruleset {
+    pub rotation: RotationSystem,
+    pub randomizer: Randomizer,
+    // Hold piece allowed
+    pub hold: bool,
+    // Shown next shapes (1..=6)
+    pub next: usize,
+    // Frames on the ground before the shape locks
+    // (0 locks it on the next row of gravity)
+    pub lock_delay: u32,
+    pub lock_reset: LockReset,
+    pub gravity: GravityCurve,
+    // Level of the game start, next level every lines_per_level lines
+    pub start_level: usize,
+    pub lines_per_level: usize,
+    pub line_gravity: LineGravity,
+    // Game over when a shape locks partly above the visible field
+    // (locking fully above it always ends the game)
+    pub partial_lock_out: bool,
+}
+
+// What restarts the lock delay
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LockReset {
+    // Move or rotation, up to the limit per row
+    // (the limit starts again on a new lowest row)
+    Move(u32),
+    // Only falling one row down
+    Step,
+    // Every move or rotation
+    Infinite,
+}
+
+impl LockReset {
+    // Lock reset by name or limit (for options)
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"step" => Some(Self::Step),
+            b"infinite" => Some(Self::Infinite),
+            _ => {
+                let mut limit = 0u32;
+                for c in name {
+                    if !c.is_ascii_digit() {
+                        return None;
+                    }
+                    limit = limit.checked_mul(10)?.checked_add((c - b'0') as u32)?;
+                }
+                Some(Self::Move(limit))
+            }
+        }
+    }
+}
+
+impl Ruleset {
+    // Modern guideline: SRS and 7-bag
+    pub fn guideline() -> Self {
+        Self {
+            rotation: RotationSystem::Srs,
+            randomizer: Randomizer::bag7(),
+            hold: true,
+            next: 5,
+            lock_delay: 30,
+            lock_reset: LockReset::Move(15),
+            gravity: GravityCurve::Guideline,
+            start_level: 1,
+            lines_per_level: 10,
+            line_gravity: LineGravity::Naive,
+            partial_lock_out: false,
+        }
+    }
+
+    // The Grand Master: ARS and 4-history randomizer
+    pub fn tgm() -> Self {
+        Self {
+            rotation: RotationSystem::Ars,
+            randomizer: Randomizer::tgm(),
+            hold: false,
+            next: 1,
+            lock_delay: 30,
+            lock_reset: LockReset::Step,
+            gravity: GravityCurve::Guideline,
+            start_level: 1,
+            lines_per_level: 10,
+            line_gravity: LineGravity::Naive,
+            partial_lock_out: false,
+        }
+    }
+
+    // NES: classic rotation and pure random
+    pub fn classic() -> Self {
+        Self {
+            rotation: RotationSystem::Nes,
+            randomizer: Randomizer::random(),
+            hold: false,
+            next: 1,
+            lock_delay: 0,
+            lock_reset: LockReset::Step,
+            gravity: GravityCurve::Classic,
+            start_level: 0,
+            lines_per_level: 10,
+            line_gravity: LineGravity::Naive,
+            partial_lock_out: false,
+        }
+    }
+
+    // Ruleset by name (for options)
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"guideline" => Some(Self::guideline()),
+            b"tgm" => Some(Self::tgm()),
+            b"classic" => Some(Self::classic()),
+            _ => None,
+        }
+    }
+}
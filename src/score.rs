@@ -0,0 +1,122 @@
+// Guideline scoring
+
+use crate::{Row, Screen, Shape, Shapes};
+
+// Points of 0..=4 lines (multiplied by level)
+const LINES: [usize; 5] = [0, 100, 300, 500, 800];
+// Points of T-spin with 0..=3 lines, full and mini
+const T_SPIN: [usize; 4] = [400, 800, 1200, 1600];
+const T_SPIN_MINI: [usize; 3] = [100, 200, 400];
+// Perfect clear bonus of 1..=4 lines and of back-to-back tetris
+const PERFECT_CLEAR: [usize; 5] = [0, 800, 1200, 1800, 2000];
+const PERFECT_CLEAR_B2B: usize = 3200;
+// Index of the fifth (last) SRS kick test, makes any spin full
+const TST_KICK: usize = 4;
+// Combo bonus per combo count (multiplied by level)
+const COMBO: usize = 50;
+pub const SOFT_DROP: usize = 1;
+pub const HARD_DROP: usize = 2;
+
+// One line clear and its points
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Clear {
+    pub lines: usize,
+    pub spin: Spin,
+    // Board is empty after it
+    pub perfect: bool,
+    // Difficult clear right after another difficult one
+    pub back_to_back: bool,
+    // Line clears in a row before this one
+    pub combo: u32,
+    pub points: usize,
+}
+
+// T-spin of the locked shape
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Spin {
+    No,
+    Mini,
+    Full,
+}
+
+// Difficult clears keep back-to-back chain
+pub fn difficult(lines: usize, spin: Spin) -> bool {
+    lines >= 4 || (lines > 0 && spin != Spin::No)
+}
+
+// Points of a line clear
+// (back-to-back gives 1.5 times points of the lines)
+pub fn points(lines: usize, spin: Spin, back_to_back: bool, combo: u32, level: usize) -> usize {
+    let level = level.max(1);
+    let points = match spin {
+        Spin::No => LINES[lines.min(4)],
+        Spin::Mini => T_SPIN_MINI[lines.min(2)],
+        Spin::Full => T_SPIN[lines.min(3)],
+    };
+    let mut points = points * level;
+    if back_to_back {
+        points = points * 3 / 2;
+    }
+    points + COMBO * combo as usize * level
+}
+
+// Perfect clear bonus (added to points of the lines)
+pub fn perfect_clear(lines: usize, back_to_back: bool, level: usize) -> usize {
+    let bonus = if lines >= 4 && back_to_back {
+        PERFECT_CLEAR_B2B
+    } else {
+        PERFECT_CLEAR[lines.min(4)]
+    };
+    bonus * level.max(1)
+}
+
+// T-spin by 3-corner rule
+// (kick is the kick of the last rotation, None when the shape moved after it;
+// 3 of 4 corners around the T center are blocked, both front corners make
+// a full T-spin, one front corner a mini unless the TST kick was used)
+pub fn t_spin<R: Row>(screen: &Screen<R>, shape: &Shape, kick: Option<usize>) -> Spin {
+    let Some(kick) = kick else {
+        return Spin::No;
+    };
+    if shape.shape != Shapes::Tshape {
+        return Spin::No;
+    }
+
+    // Center is the cell with 3 neighbours, T points away from the empty one
+    let filled = |x: i32, y: i32| {
+        (0..shape.dx as i32).contains(&x)
+            && (0..shape.dy as i32).contains(&y)
+            && shape.canvas[x as usize + y as usize * shape.dx]
+    };
+    let sides = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+    let mut center = None;
+    for y in 0..shape.dy as i32 {
+        for x in 0..shape.dx as i32 {
+            let mut back = sides.iter().filter(|&&(i, j)| !filled(x + i, y + j));
+            if let (true, Some(&side), None) = (filled(x, y), back.next(), back.next()) {
+                center = Some((x, y, side));
+            }
+        }
+    }
+    let Some((x, y, (back_x, back_y))) = center else {
+        return Spin::No;
+    };
+
+    let (x, y) = (shape.x + x, shape.y + y);
+    let blocked = |i: i32, j: i32| screen.blocked(x + i, y + j);
+    let corners = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+    let count = corners.iter().filter(|&&(i, j)| blocked(i, j)).count();
+    if count < 3 {
+        return Spin::No;
+    }
+    // Front corners are on the side opposite to the back
+    let front = corners
+        .iter()
+        .filter(|&&(i, j)| i != back_x && j != back_y && blocked(i, j))
+        .count();
+    if front == 2 || kick == TST_KICK {
+        Spin::Full
+    } else {
+        Spin::Mini
+    }
+}
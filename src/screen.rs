@@ -1,233 +1,398 @@
 // Import Shapes structs
-use crate::Shape;
+use crate::shape::SHAPES;
+use crate::{Row, Shape, Shapes};
 
-// For all system (for android *const u8)
-type Cstr = *const i8;
+// Tallest board (vanish zone included)
+pub const MAX_HEIGHT: usize = 64;
 
-// Import libc types
-extern crate libc;
-use libc::{c_char, c_int};
-
-#[cfg(target_os = "linux")]
-#[link(name = "c")]
+// What happens to cells above cleared lines
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineGravity {
+    // Rows above fall by the count of cleared lines below them
+    Naive,
+    // Connected chunks fall as far as they can (once)
+    Sticky,
+    // Chunks fall like sticky and new full lines clear again
+    Cascade,
+}
 
-// Link libc funcs
-extern "C" {
-    fn system(s: *const c_char) -> c_int;
-    fn printf(format: *const c_char, ...) -> c_int;
+impl LineGravity {
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"naive" => Some(Self::Naive),
+            b"sticky" => Some(Self::Sticky),
+            b"cascade" => Some(Self::Cascade),
+            _ => None,
+        }
+    }
 }
 
-// Import Box<T> and Vec<T>
-extern crate alloc;
-use alloc::{boxed::Box, vec, vec::Vec};
+// Bits of the shape kind index of a cell (bitplanes)
+const KIND_BITS: usize = 3;
 
-pub struct Screen {
-    pub rows: usize,
-    pub cols: usize,
-    pub sc: Box<[bool]>,
+// Cells of rows as u64 masks (chunks of the board)
+type Chunk = [u64; MAX_HEIGHT];
+
+// Locked cells of the board, one bitmask per row
+// (the falling shape is not stored here, see Game;
+// the first `hidden` rows are the vanish zone above the visible field)
+pub struct Screen<R: Row = u16> {
+    pub width: usize,
+    pub height: usize,
+    pub hidden: usize,
+    // Rows from the top, only the first `height` are used
+    pub rows: [R; MAX_HEIGHT],
+    // Kind of every filled cell: bit k of its Shapes index is in plane k
+    pub kinds: [[R; KIND_BITS]; MAX_HEIGHT],
+    // Row with all cells filled
+    full: R,
 }
 
-impl Screen {
-    pub fn new(r: usize, c: usize) -> Self {
+impl<R: Row> Screen<R> {
+    pub fn new(width: usize, height: usize, hidden: usize) -> Self {
+        assert!(width <= R::BITS && height <= MAX_HEIGHT);
+        let full = if width == 64 { !0 } else { (1u64 << width) - 1 };
         Self {
-            rows: r,
-            cols: c,
-            sc: vec![false; r * c].into_boxed_slice(),
+            width,
+            height,
+            hidden,
+            rows: [R::default(); MAX_HEIGHT],
+            kinds: [[R::default(); KIND_BITS]; MAX_HEIGHT],
+            full: R::from_bits(full),
         }
     }
 
-    pub fn print(&self, scores: usize, lines: usize, shape: &Shape) {
-        unsafe {
-            // Clear console
-            system("clear\0".as_ptr() as Cstr);
-            // Move cursor to 0; 0
-            printf("\x1b[H\0".as_ptr() as Cstr);
+    // Is cell (x; y) filled?
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.rows[y].bits() >> x & 1 == 1
+    }
+
+    // Kind of shape which left cell (x; y), None if it is empty
+    pub fn kind(&self, x: usize, y: usize) -> Option<Shapes> {
+        if !self.get(x, y) {
+            return None;
         }
+        let planes = self.kinds[y].iter().enumerate();
+        let index = planes.fold(0, |index, (k, plane)| index | (plane.bits() >> x & 1) << k);
+        SHAPES.get(index as usize).copied()
+    }
 
-        for i in 0..self.rows * self.cols {
-            unsafe {
-                if i % self.rows == 0 {
-                    printf("\n\0".as_ptr() as Cstr);
-                }
+    // Fill cell (x; y) by shape kind
+    pub fn set(&mut self, x: usize, y: usize, kind: Shapes) {
+        self.fill(y, R::from_bits(1 << x), kind);
+    }
 
-                if self.sc[i] {
-                    printf("[] \0".as_ptr() as Cstr);
-                } else {
-                    printf(".. \0".as_ptr() as Cstr);
-                }
-            }
+    // Fill cells of mask in row y by shape kind
+    fn fill(&mut self, y: usize, mask: R, kind: Shapes) {
+        self.rows[y] = self.rows[y] | mask;
+        for (k, plane) in self.kinds[y].iter_mut().enumerate() {
+            let bit = if kind as usize >> k & 1 == 1 {
+                mask
+            } else {
+                R::default()
+            };
+            *plane = (*plane & !mask) | bit;
+        }
+    }
+
+    // Is cell (x; y) out of the screen or filled?
+    // (above the screen is free, shapes can rotate there)
+    pub fn blocked(&self, x: i32, y: i32) -> bool {
+        // End of the screen
+        if x < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return true;
+        }
+        if y < 0 {
+            return false;
         }
 
-        unsafe {
-            printf(
-                "\nSCORES: %d; LINES: %d\n\nNEXT SHAPE:\0".as_ptr() as Cstr,
-                scores,
-                lines,
-            );
-            shape.print();
+        // Collision with another figure
+        self.get(x as usize, y as usize)
+    }
+
+    // Row mask of a shape moved to column x
+    // (None if a cell goes out of the sides)
+    fn place(&self, mask: u64, x: i32) -> Option<R> {
+        if x.unsigned_abs() >= 64 {
+            return (mask == 0).then(R::default);
         }
+        let (placed, back) = if x < 0 {
+            let placed = mask >> -x;
+            (placed, placed << -x)
+        } else {
+            let placed = mask << x;
+            (placed, placed >> x)
+        };
+        if back != mask || placed & !self.full.bits() != 0 {
+            return None;
+        }
+        Some(R::from_bits(placed))
     }
 
     // Put shape to screen
     // (unsafe, u need to use updatable for safe use put)
     pub fn put(&mut self, shape: &Shape) {
-        for i in 0..shape.dx {
-            for j in 0..shape.dy {
-                if shape.canvas[i + j * shape.dx]
-                    && !self.sc[(shape.x as usize + i) + (shape.y as usize + j) * self.rows]
-                {
-                    self.sc[(shape.x as usize + i) + (shape.y as usize + j) * self.rows] = true;
-                }
+        for j in 0..shape.dy {
+            let y = shape.y + j as i32;
+            if !(0..self.height as i32).contains(&y) {
+                continue;
+            }
+            if let Some(placed) = self.place(shape.row(j), shape.x) {
+                self.fill(y as usize, placed, shape.shape);
             }
         }
     }
 
     // Can shape be putted to screen?
-    fn updatable(&self, shape: &Shape) -> Result<(), ()> {
-        for i in 0..shape.dx {
-            for j in 0..shape.dy {
-                if shape.canvas[i + j * shape.dx] {
-                    // End of the screen
-                    if shape.x as usize + i + (shape.y as usize + j) * self.rows
-                        >= self.rows * self.cols
-                    {
-                        return Err(());
-                    }
-
-                    // Collision with another figure
-                    if self.sc[(shape.x as usize + i) + (shape.y as usize + j) * self.rows] {
-                        return Err(());
-                    }
-                }
+    #[allow(clippy::result_unit_err)]
+    pub fn updatable(&self, shape: &Shape) -> Result<(), ()> {
+        for j in 0..shape.dy {
+            let mask = shape.row(j);
+            if mask == 0 {
+                continue;
+            }
+            let y = shape.y + j as i32;
+            let placed = self.place(mask, shape.x).ok_or(())?;
+            if y >= self.height as i32 {
+                return Err(());
+            }
+            if y >= 0 && self.rows[y as usize] & placed != R::default() {
+                return Err(());
             }
         }
 
         Ok(())
     }
 
-    // Delete shape from screen
-    fn delete(&mut self, shape: &Shape) {
-        for i in 0..shape.dx {
-            for j in 0..shape.dy {
-                if shape.canvas[i + j * shape.dx] {
-                    self.sc[(shape.x as usize + i) + (shape.y as usize + j) * self.rows] = false;
-                }
+    // Return lines which need to delete (bit y is the row y)
+    fn full_lines(&self) -> u64 {
+        let mut lines = 0;
+        for (y, &row) in self.rows[..self.height].iter().enumerate() {
+            if row == self.full {
+                lines |= 1 << y;
             }
         }
+        lines
     }
 
-    // Return lines which need to delete
-    fn full_lines(&self) -> Vec<usize> {
-        let mut result = vec![];
-        let mut full;
-        for i in 0..self.cols {
-            full = true;
-            for j in 0..self.rows {
-                if !self.sc[j + i * self.rows] {
-                    full = false;
-                    break;
-                }
-            }
-            if full {
-                result.push(i);
+    // Delete lines, every row above falls by the deleted lines below it
+    fn collapse(&mut self, lines: u64) {
+        let mut to = self.height;
+        for y in (0..self.height).rev() {
+            if lines >> y & 1 == 0 {
+                to -= 1;
+                self.rows[to] = self.rows[y];
+                self.kinds[to] = self.kinds[y];
             }
         }
-        result
+        self.rows[..to].fill(R::default());
+        self.kinds[..to].fill([R::default(); KIND_BITS]);
     }
 
-    // Delete line
-    fn clear_line(&mut self, line: usize) {
-        for i in 0..self.rows {
-            self.sc[i] = false;
-        }
-        for i in 1..line {
-            for j in 0..self.rows {
-                self.sc[j + (line - i + 1) * self.rows] = self.sc[j + (line - i) * self.rows];
+    // Empty lines without moving the rest
+    fn empty(&mut self, lines: u64) {
+        for y in 0..self.height {
+            if lines >> y & 1 == 1 {
+                self.rows[y] = R::default();
+                self.kinds[y] = [R::default(); KIND_BITS];
             }
         }
     }
 
     // Delete all lines which need to delete
-    fn clear_lines(&mut self) -> (usize, usize) {
+    // (return count of deleted lines, chain clears of cascade included)
+    pub fn clear_lines(&mut self, gravity: LineGravity) -> usize {
         let lines = self.full_lines();
-        for i in 0..lines.len() {
-            self.clear_line(lines[i]);
-        }
-        return (lines.len() * lines.len() * 10, lines.len());
-    }
-
-    // Update game loop
-    // (move shape down, can return game over, can make new shape)
-    pub fn move_down(&mut self, shape: &mut Shape) -> Result<(Shape, bool, usize, usize), ()> {
-        self.delete(shape);
-        shape.y += 1;
-        let (mut lines, mut scores) = (0, 0);
-
-        match self.updatable(&shape) {
-            Ok(()) => {
-                self.put(&shape);
-            }
-            Err(()) => {
-                shape.y -= 1;
-                self.put(shape);
-                (scores, lines) = self.clear_lines();
-                let new_shape = Shape::new(self.rows as i32);
-                match self.updatable(&new_shape) {
-                    Err(()) => return Err(()),
-                    Ok(()) => return Ok((new_shape, true, scores, lines)),
+        if lines == 0 {
+            return 0;
+        }
+        match gravity {
+            LineGravity::Naive => self.collapse(lines),
+            LineGravity::Sticky => {
+                self.empty(lines);
+                self.settle();
+            }
+            LineGravity::Cascade => {
+                let mut count = 0;
+                let mut chain = lines;
+                while chain != 0 {
+                    count += chain.count_ones() as usize;
+                    self.empty(chain);
+                    self.settle();
+                    chain = self.full_lines();
                 }
+                return count;
             }
         }
-
-        Ok((shape.clone(), false, scores, lines))
+        lines.count_ones() as usize
     }
 
-    // Rotate figure
-    pub fn rotate(&mut self, shape: &mut Shape, rotate: i32) -> Shape {
-        self.delete(shape);
-
-        let (minx, maxx) = shape.clone().rotate(rotate).minmax();
-        if shape.x + minx < 0 || shape.x + maxx > self.rows as i32 - 1 {
-            self.put(shape);
-            return shape.clone();
+    // Connected cells of (x; y), grown through the 4 sides
+    fn chunk(&self, x: usize, y: usize) -> Chunk {
+        let mut chunk = [0; MAX_HEIGHT];
+        chunk[y] = 1 << x;
+        let mut grown = true;
+        while grown {
+            grown = false;
+            for y in 0..self.height {
+                let above = if y > 0 { chunk[y - 1] } else { 0 };
+                let below = if y + 1 < self.height { chunk[y + 1] } else { 0 };
+                let row = chunk[y];
+                let next = (row | row << 1 | row >> 1 | above | below) & self.rows[y].bits();
+                if next != row {
+                    chunk[y] = next;
+                    grown = true;
+                }
+            }
         }
+        chunk
+    }
 
-        match self.updatable(&(&mut shape.clone()).rotate(rotate)) {
-            Ok(()) => {
-                self.put(&shape.rotate(rotate));
+    // Let every chunk fall as far as it can
+    // (lower chunks first, again until nothing moves)
+    fn settle(&mut self) {
+        let mut moved = true;
+        while moved {
+            moved = false;
+            let mut left: Chunk = [0; MAX_HEIGHT];
+            for (y, row) in left[..self.height].iter_mut().enumerate() {
+                *row = self.rows[y].bits();
             }
-            Err(()) => {
-                self.put(shape);
+            while let Some(y) = (0..self.height).rev().find(|&y| left[y] != 0) {
+                let chunk = self.chunk(left[y].trailing_zeros() as usize, y);
+                // Take chunk off the board with its kinds
+                let mut kinds = [[R::default(); KIND_BITS]; MAX_HEIGHT];
+                for y in 0..self.height {
+                    let mask = R::from_bits(chunk[y]);
+                    left[y] &= !chunk[y];
+                    self.rows[y] = self.rows[y] & !mask;
+                    for (k, plane) in self.kinds[y].iter_mut().enumerate() {
+                        kinds[y][k] = *plane & mask;
+                        *plane = *plane & !mask;
+                    }
+                }
+                let fall = self.fall_distance(&chunk);
+                for y in (0..self.height - fall).rev() {
+                    let to = y + fall;
+                    self.rows[to] = self.rows[to] | R::from_bits(chunk[y]);
+                    for (k, plane) in self.kinds[to].iter_mut().enumerate() {
+                        *plane = *plane | kinds[y][k];
+                    }
+                }
+                moved |= fall > 0;
             }
         }
+    }
 
-        shape.clone()
+    // How many rows chunk (taken off the board) can fall
+    fn fall_distance(&self, chunk: &Chunk) -> usize {
+        let mut fall = 0;
+        loop {
+            let next = fall + 1;
+            let blocked = (0..self.height).any(|y| {
+                chunk[y] != 0
+                    && (y + next >= self.height || self.rows[y + next].bits() & chunk[y] != 0)
+            });
+            if blocked {
+                return fall;
+            }
+            fall = next;
+        }
     }
 
-    // Move figure left/right
-    pub fn move_side(&mut self, shape: &mut Shape, side: i32) -> Shape {
-        self.delete(shape);
-        shape.x += side;
+    // No cells left (perfect clear after line clears)
+    pub fn is_empty(&self) -> bool {
+        self.rows[..self.height]
+            .iter()
+            .all(|&row| row == R::default())
+    }
+}
 
-        let (minx, maxx) = shape.minmax();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::SHAPES;
 
-        if shape.x + minx < 0 {
-            shape.x = -minx;
-        }
-        if shape.x + maxx > self.rows as i32 - 1 {
-            shape.x = self.rows as i32 - maxx - 1;
+    // Screen from rows of text ('X' filled, with kinds in columns)
+    fn board(rows: &[&str]) -> Screen {
+        let mut screen = Screen::new(4, rows.len(), 0);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, ch) in row.bytes().enumerate() {
+                if ch == b'X' {
+                    screen.set(x, y, SHAPES[x]);
+                }
+            }
         }
+        screen
+    }
 
-        match self.updatable(&shape) {
-            Ok(()) => {
-                self.put(&shape);
-            }
-            Err(()) => {
-                shape.x -= side;
-                self.put(shape);
+    fn rows(screen: &Screen) -> [[u8; 4]; 8] {
+        let mut rows = [[b'.'; 4]; 8];
+        for (y, row) in rows.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                if screen.get(x, y) {
+                    *cell = b'X';
+                }
             }
         }
+        rows
+    }
+
+    fn text(lines: [&str; 8]) -> [[u8; 4]; 8] {
+        lines.map(|line| line.as_bytes().try_into().unwrap())
+    }
+
+    // Full lines 5 and 7 with blocks between and over them
+    const APART: [&str; 8] = [
+        "....", "....", "....", "....", "X...", "XXXX", "..X.", "XXXX",
+    ];
+    // One full line 6, a block over a hole
+    const CHAIN: [&str; 8] = [
+        "....", "....", "....", "....", "....", "..X.", "XXXX", "XX.X",
+    ];
+
+    #[test]
+    fn naive_clears_lines_apart() {
+        let mut screen = board(&APART);
+        assert_eq!(screen.clear_lines(LineGravity::Naive), 2);
+        assert_eq!(
+            rows(&screen),
+            text(["....", "....", "....", "....", "....", "....", "X...", "..X."])
+        );
+        // Kinds go down with the cells
+        assert_eq!(screen.kind(0, 6), Some(SHAPES[0]));
+        assert_eq!(screen.kind(2, 7), Some(SHAPES[2]));
+    }
+
+    #[test]
+    fn sticky_chunks_fall() {
+        let mut screen = board(&APART);
+        assert_eq!(screen.clear_lines(LineGravity::Sticky), 2);
+        assert_eq!(
+            rows(&screen),
+            text(["....", "....", "....", "....", "....", "....", "....", "X.X."])
+        );
+
+        let mut screen = board(&CHAIN);
+        assert_eq!(screen.clear_lines(LineGravity::Sticky), 1);
+        assert_eq!(
+            rows(&screen),
+            text(["....", "....", "....", "....", "....", "....", "....", "XXXX"])
+        );
+    }
+
+    #[test]
+    fn cascade_counts_chain() {
+        let mut naive = board(&CHAIN);
+        assert_eq!(naive.clear_lines(LineGravity::Naive), 1);
+        assert_eq!(
+            rows(&naive),
+            text(["....", "....", "....", "....", "....", "....", "..X.", "XX.X"])
+        );
 
-        shape.clone()
+        // The fallen block fills line 7, which clears too
+        let mut screen = board(&CHAIN);
+        assert_eq!(screen.clear_lines(LineGravity::Cascade), 2);
+        assert!(screen.is_empty());
     }
 }
@@ -1,141 +1,53 @@
-// Import Box<T> and Vec<T>
-use alloc::{boxed::Box, vec, vec::Vec};
+use crate::rotation::RotationSystem;
 
-// Import libc types
-type Cstr = *const i8;
-
-extern crate libc;
-use libc::{c_char, c_int};
-
-#[cfg(target_os = "linux")]
-#[link(name = "c")]
-
-// Link libc funcs
-extern "C" {
-    fn rand() -> c_int;
-    fn printf(format: *const c_char, ...) -> c_int;
-}
-
-#[derive(Clone)]
+// Falling shape, a copy is cheap
+// (canvas is the rotation table of the rotation system)
+#[derive(Clone, Copy)]
 pub struct Shape {
-    shape: Shapes,
+    pub shape: Shapes,
     pub x: i32,
     pub y: i32,
     pub dx: usize,
     pub dy: usize,
     pub rotate: i32,
-    pub canvas: Box<[bool]>,
+    pub canvas: &'static [bool],
+    // Masks of canvas rows (see row())
+    pub rows: &'static [u8; 4],
 }
 
 impl Shape {
-    pub fn new(sc_dx: i32) -> Self {
-        let rand_num = unsafe { rand() } as usize;
-        match _SHAPES[rand_num % _SHAPES.len()] {
-            Shapes::Tshape => {
-                return Self {
-                    shape: Shapes::Tshape,
-                    x: sc_dx / 2 - 2,
-                    y: 0,
-                    dx: 3,
-                    dy: 3,
-                    rotate: 0,
-                    canvas: vec![false, false, false, true, true, true, false, true, false]
-                        .into_boxed_slice(),
-                }
-            }
-            Shapes::Ishape => {
-                return Self {
-                    shape: Shapes::Ishape,
-                    x: sc_dx / 2 - 2,
-                    y: 0,
-                    dx: 4,
-                    dy: 4,
-                    rotate: 0,
-                    canvas: vec![
-                        false, false, false, false, true, true, true, true, false, false, false,
-                        false, false, false, false, false,
-                    ]
-                    .into_boxed_slice(),
-                }
-            }
-            Shapes::Oshape => {
-                return Self {
-                    shape: Shapes::Oshape,
-                    x: sc_dx / 2 - 2,
-                    y: 0,
-                    dx: 2,
-                    dy: 2,
-                    rotate: 0,
-                    canvas: vec![true, true, true, true].into_boxed_slice(),
-                }
-            }
-            Shapes::Sshape => {
-                return Self {
-                    shape: Shapes::Sshape,
-                    x: sc_dx / 2 - 2,
-                    y: 0,
-                    dx: 3,
-                    dy: 3,
-                    rotate: 0,
-                    canvas: vec![false, true, true, true, true, false, false, false, false]
-                        .into_boxed_slice(),
-                }
-            }
-            Shapes::Zshape => {
-                return Self {
-                    shape: Shapes::Zshape,
-                    x: sc_dx / 2 - 2,
-                    y: 0,
-                    dx: 3,
-                    dy: 3,
-                    rotate: 0,
-                    canvas: vec![true, true, false, false, true, true, false, false, false]
-                        .into_boxed_slice(),
-                }
-            }
-            Shapes::Jshape => {
-                return Self {
-                    shape: Shapes::Jshape,
-                    x: sc_dx / 2 - 2,
-                    y: 0,
-                    dx: 3,
-                    dy: 3,
-                    rotate: 0,
-                    canvas: vec![true, false, false, true, true, true, false, false, false]
-                        .into_boxed_slice(),
-                }
-            }
-            Shapes::Lshape => {
-                return Self {
-                    shape: Shapes::Lshape,
-                    x: sc_dx / 2 - 2,
-                    y: 0,
-                    dx: 3,
-                    dy: 3,
-                    rotate: 0,
-                    canvas: vec![false, false, true, true, true, true, false, false, false]
-                        .into_boxed_slice(),
-                }
-            }
+    pub fn new(sc_dx: i32, shape: Shapes, system: RotationSystem) -> Self {
+        let canvas = system.canvas(shape, 0);
+        let dx = if canvas.len() == 4 {
+            2
+        } else if canvas.len() == 9 {
+            3
+        } else {
+            4
+        };
+
+        // Top of shape at the top of screen
+        let top = canvas.iter().position(|&c| c).unwrap_or(0) / dx;
+
+        Self {
+            shape,
+            x: sc_dx / 2 - dx.div_ceil(2) as i32,
+            y: -(top as i32),
+            dx,
+            dy: dx,
+            rotate: 0,
+            canvas,
+            rows: system.rows(shape, 0),
         }
     }
 
-    pub fn print(&self) {
-        for i in 0..(self.dx * self.dy) as usize {
-            unsafe {
-                if i % self.dx == 0 {
-                    printf("\n\0".as_ptr() as Cstr);
-                }
-                if self.canvas[i] {
-                    printf("## \0".as_ptr() as Cstr);
-                } else {
-                    printf(".. \0".as_ptr() as Cstr);
-                }
-            }
-        }
-        unsafe {
-            printf("\n\0".as_ptr() as Cstr);
+    // Is screen cell (x; y) covered by this shape?
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (i, j) = (x - self.x, y - self.y);
+        if i < 0 || j < 0 || i >= self.dx as i32 || j >= self.dy as i32 {
+            return false;
         }
+        self.canvas[i as usize + j as usize * self.dx]
     }
 
     // Just examples:
@@ -166,96 +78,35 @@ impl Shape {
         (minx, maxx)
     }
 
-    // Rotate shapes
-    pub fn rotate(&mut self, rotate: i32) -> Self {
-        self.rotate += rotate;
-        if self.rotate < 0 {
-            self.rotate += 4;
-        }
-        if self.rotate > 3 {
-            self.rotate -= 4;
-        }
+    // Cells of canvas row j as a mask (bit i is the column i)
+    pub fn row(&self, j: usize) -> u64 {
+        self.rows[j] as u64
+    }
 
-        let allrotates: Vec<Vec<bool>>;
-        match self.shape {
-            Shapes::Tshape => {
-                allrotates = vec![
-                    vec![false, false, false, true, true, true, false, true, false],
-                    vec![false, true, false, true, true, false, false, true, false],
-                    vec![false, true, false, true, true, true, false, false, false],
-                    vec![false, true, false, false, true, true, false, true, false],
-                ];
-            }
-            Shapes::Ishape => {
-                allrotates = vec![
-                    vec![
-                        false, false, false, false, true, true, true, true, false, false, false,
-                        false, false, false, false, false,
-                    ],
-                    vec![
-                        false, false, true, false, false, false, true, false, false, false, true,
-                        false, false, false, true, false,
-                    ],
-                    vec![
-                        false, false, false, false, false, false, false, false, true, true, true,
-                        true, false, false, false, false,
-                    ],
-                    vec![
-                        false, true, false, false, false, true, false, false, false, true, false,
-                        false, false, true, false, false,
-                    ],
-                ];
-            }
-            Shapes::Oshape => {
-                allrotates = vec![
-                    vec![true, true, true, true],
-                    vec![true, true, true, true],
-                    vec![true, true, true, true],
-                    vec![true, true, true, true],
-                ];
-            }
-            Shapes::Sshape => {
-                allrotates = vec![
-                    vec![false, true, true, true, true, false, false, false, false],
-                    vec![false, true, false, false, true, true, false, false, true],
-                    vec![false, false, false, false, true, true, true, true, false],
-                    vec![true, false, false, true, true, false, false, true, false],
-                ];
-            }
-            Shapes::Zshape => {
-                allrotates = vec![
-                    vec![true, true, false, false, true, true, false, false, false],
-                    vec![false, false, true, false, true, true, false, true, false],
-                    vec![false, false, false, true, true, false, false, true, true],
-                    vec![false, true, false, true, true, false, true, false, false],
-                ];
-            }
-            Shapes::Jshape => {
-                allrotates = vec![
-                    vec![true, false, false, true, true, true, false, false, false],
-                    vec![false, true, true, false, true, false, false, true, false],
-                    vec![false, false, false, true, true, true, false, false, true],
-                    vec![false, true, false, false, true, false, true, true, false],
-                ];
-            }
-            Shapes::Lshape => {
-                allrotates = vec![
-                    vec![false, false, true, true, true, true, false, false, false],
-                    vec![false, true, false, false, true, false, false, true, true],
-                    vec![false, false, false, true, true, true, true, false, false],
-                    vec![true, true, false, false, true, false, false, true, false],
-                ];
-            }
-        }
+    // Screen rows of the highest and the lowest cells
+    pub fn cells_rows(&self) -> (i32, i32) {
+        let rows = self.canvas.chunks(self.dx).enumerate();
+        let mut filled = rows
+            .filter(|(_, row)| row.contains(&true))
+            .map(|(j, _)| j as i32);
+        let top = filled.next().unwrap_or(0);
+        let bottom = filled.next_back().unwrap_or(top);
+        (self.y + top, self.y + bottom)
+    }
 
-        self.canvas = allrotates[self.rotate as usize].clone().into_boxed_slice();
-        self.clone()
+    // Rotate shapes
+    // (canvas of the new rotation state from the rotation system)
+    pub fn rotate(mut self, rotate: i32, system: RotationSystem) -> Self {
+        self.rotate = (self.rotate + rotate).rem_euclid(4);
+        self.canvas = system.canvas(self.shape, self.rotate);
+        self.rows = system.rows(self.shape, self.rotate);
+        self
     }
 }
 
 // All shapes
-#[derive(Clone, Copy)]
-enum Shapes {
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shapes {
     Tshape,
     Ishape,
     Oshape,
@@ -265,7 +116,7 @@ enum Shapes {
     Lshape,
 }
 
-const _SHAPES: [Shapes; 7] = [
+pub const SHAPES: [Shapes; 7] = [
     Shapes::Tshape,
     Shapes::Ishape,
     Shapes::Oshape,
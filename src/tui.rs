@@ -0,0 +1,8 @@
+// Terminal frontend parts (binary only, the engine is in lib.rs)
+
+pub mod clock;
+pub mod input;
+pub mod options;
+pub mod render;
+pub mod term;
+pub mod theme;
@@ -0,0 +1,51 @@
+// Import libc types
+extern crate libc;
+use libc::{c_int, clockid_t, timespec, CLOCK_MONOTONIC};
+
+// Import game engine
+use tetris::gravity::FRAMES_PER_SECOND;
+
+// Link libc funcs
+#[cfg(target_os = "linux")]
+#[link(name = "c")]
+extern "C" {
+    fn clock_gettime(clk_id: clockid_t, tp: *mut timespec) -> c_int;
+}
+
+// One game frame
+pub const FRAME_NS: u64 = 1_000_000_000 / FRAMES_PER_SECOND as u64;
+
+// Monotonic time in ns (never jumps with the wall clock)
+pub fn now() -> u64 {
+    let mut tp = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        clock_gettime(CLOCK_MONOTONIC, &mut tp);
+    }
+    tp.tv_sec as u64 * 1_000_000_000 + tp.tv_nsec as u64
+}
+
+// Milliseconds to frames (rounded, huge times are u32::MAX frames)
+pub fn frames(ms: u64) -> u32 {
+    let frames = ms
+        .saturating_mul(FRAMES_PER_SECOND as u64)
+        .saturating_add(500)
+        / 1000;
+    frames.min(u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ms_to_frames() {
+        assert_eq!(frames(0), 0);
+        assert_eq!(frames(167), 10);
+        assert_eq!(frames(1000), 60);
+        assert_eq!(frames(u64::MAX / 2), u32::MAX);
+        assert_eq!(frames(u64::MAX), u32::MAX);
+    }
+}
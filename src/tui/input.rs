@@ -0,0 +1,446 @@
+// Import game engine
+use tetris::{Game, Key};
+
+use crate::tui::term;
+use core::sync::atomic::Ordering;
+
+// Import libc types and a few funcs/macroses
+extern crate libc;
+use libc::{
+    c_int, c_void, fd_set, size_t, ssize_t, timeval, FD_ISSET, FD_SET, FD_ZERO, SIGINT, SIGTSTP,
+    STDIN_FILENO, STDOUT_FILENO,
+};
+
+// Link libc funcs
+#[cfg(target_os = "linux")]
+#[link(name = "c")]
+extern "C" {
+    fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+    fn select(
+        nfds: c_int,
+        readfds: *mut fd_set,
+        writefds: *mut fd_set,
+        errorfds: *mut fd_set,
+        timeout: *mut timeval,
+    ) -> c_int;
+}
+
+// Key codes out of unicode for keys without a character
+// (arrays are "27 91 ... Key_code", Key_code is A, B, C or D)
+const ARRAY: u32 = 0x11_0000;
+const UP: u32 = ARRAY + b'A' as u32;
+const DOWN: u32 = ARRAY + b'B' as u32;
+const RIGHT: u32 = ARRAY + b'C' as u32;
+const LEFT: u32 = ARRAY + b'D' as u32;
+// Ctrl+C and Ctrl+Z in kitty protocol: 'c' and 'z' with ctrl modifier
+const CTRL: u32 = 0x12_0000;
+const CTRL_C: u32 = CTRL + b'c' as u32;
+const CTRL_Z: u32 = CTRL + b'z' as u32;
+
+// Codes of terminal keys to game keys
+fn key(code: u32) -> Option<Key> {
+    match code {
+        LEFT => Some(Key::Left),
+        RIGHT => Some(Key::Right),
+        DOWN => Some(Key::SoftDrop),
+        // X
+        UP | 120 => Some(Key::RotateCw),
+        // Z
+        122 => Some(Key::RotateCcw),
+        // C
+        99 => Some(Key::Hold),
+        // Space
+        32 => Some(Key::HardDrop),
+        _ => None,
+    }
+}
+
+// Event type of kitty keyboard protocol
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Event {
+    Press,
+    Repeat,
+    Release,
+}
+
+// Without kitty protocol terminal gives only bytes, so held keys are
+// guessed by its autorepeat: a second byte after the delay and a third
+// one soon after it mean the key is held since the first one, no bytes
+// for a while means it is released. Bytes before that are taps.
+
+// Longest terminal delay before autorepeat starts (frames)
+const REPEAT_DELAY: u32 = 42;
+// Longest time between two autorepeat bytes (frames)
+const REPEAT_GAP: u32 = 6;
+
+#[derive(Clone, Copy, Default)]
+struct KeyState {
+    held: bool,
+    // Taps which may be the start of autorepeat (up to 2)
+    taps: u32,
+    // Frames between the first and the second of them
+    tap_frames: u32,
+    idle_frames: u32,
+}
+
+// Keyboard of the game: kitty key events or held keys model over bytes
+pub struct Input {
+    kitty: bool,
+    keys: [KeyState; 7],
+    // Bytes of a sequence not read to the end yet
+    pending: [u8; 64],
+    len: usize,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            kitty: false,
+            keys: [KeyState::default(); 7],
+            pending: [0; 64],
+            len: 0,
+        }
+    }
+
+    // Turn on kitty keyboard protocol if the terminal knows it
+    // (asks current flags, then primary device attributes which every
+    // terminal answers; kitty answer comes first when supported)
+    pub fn enable_kitty(&mut self) {
+        send(b"\x1b[?u\x1b[c");
+        let mut answer = [0u8; 64];
+        let mut len = 0;
+        while len < answer.len() && wait_stdin(200_000) {
+            let n = unsafe {
+                read(
+                    STDIN_FILENO,
+                    answer[len..].as_mut_ptr() as *mut c_void,
+                    answer.len() - len,
+                )
+            };
+            if n <= 0 {
+                break;
+            }
+            len += n as usize;
+            if let Some(end) = find_da(&answer[..len]) {
+                self.kitty = find_kitty(&answer[..end]);
+                break;
+            }
+        }
+        if self.kitty {
+            send(term::KITTY_ON);
+            term::KITTY.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // Give back the keyboard mode of the terminal
+    pub fn disable_kitty(&mut self) {
+        if self.kitty {
+            send(term::KITTY_OFF);
+            self.kitty = false;
+            term::KITTY.store(false, Ordering::SeqCst);
+        }
+    }
+
+    // Wait up to usec for keys and read them (true if any game key came)
+    pub fn read(&mut self, game: &mut Game, usec: i64) -> bool {
+        if !wait_stdin(usec) {
+            return false;
+        }
+        let n = unsafe {
+            read(
+                STDIN_FILENO,
+                self.pending[self.len..].as_mut_ptr() as *mut c_void,
+                self.pending.len() - self.len,
+            )
+        };
+        if n <= 0 {
+            return false;
+        }
+        self.len += n as usize;
+
+        let mut hit = false;
+        let mut start = 0;
+        while start < self.len {
+            let (used, code, event) = match parse(&self.pending[start..self.len]) {
+                Some(parsed) => parsed,
+                None if self.len - start < self.pending.len() => break,
+                // Garbage fills the whole buffer
+                None => (self.len - start, 0, Event::Press),
+            };
+            start += used;
+            hit |= self.event(code, event, game);
+        }
+        self.pending.copy_within(start..self.len, 0);
+        self.len -= start;
+        hit
+    }
+
+    fn event(&mut self, code: u32, event: Event, game: &mut Game) -> bool {
+        if self.kitty && matches!(code, CTRL_C | CTRL_Z) {
+            // Kitty protocol sends Ctrl+C and Ctrl+Z as keys, not as signals
+            if event == Event::Press {
+                let sig = if code == CTRL_C { SIGINT } else { SIGTSTP };
+                unsafe {
+                    term::raise(sig);
+                }
+            }
+            return false;
+        }
+        let Some(key) = key(code) else {
+            return false;
+        };
+        if !self.kitty {
+            self.hit(key, game);
+            return true;
+        }
+        match event {
+            Event::Press => game.press(key),
+            // Engine repeats held keys itself
+            Event::Repeat => return false,
+            Event::Release => game.release(key),
+        }
+        true
+    }
+
+    // Byte of key came from the terminal (without kitty protocol)
+    fn hit(&mut self, key: Key, game: &mut Game) {
+        if !matches!(key, Key::Left | Key::Right | Key::SoftDrop) {
+            game.press(key);
+            game.release(key);
+            return;
+        }
+
+        let state = &mut self.keys[key as usize];
+        if state.held {
+            // Autorepeat of held key
+        } else if state.taps == 2 && state.idle_frames <= REPEAT_GAP {
+            // Autorepeat goes on: key is held since the first tap
+            // (taps moved already)
+            state.taps = 0;
+            state.held = true;
+            game.press_held(key, state.tap_frames + state.idle_frames);
+        } else {
+            // Maybe just a tap, maybe the end of autorepeat delay
+            if state.taps > 0 && state.idle_frames <= REPEAT_DELAY {
+                state.taps = 2;
+                state.tap_frames = state.idle_frames;
+            } else {
+                state.taps = 1;
+            }
+            game.press(key);
+            game.release(key);
+        }
+        state.idle_frames = 0;
+    }
+
+    // Forget held keys (their releases are lost while the game is stopped)
+    pub fn release_all(&mut self, game: &mut Game) {
+        for key in [Key::Left, Key::Right, Key::SoftDrop] {
+            self.keys[key as usize] = KeyState::default();
+            game.release(key);
+        }
+    }
+
+    // Time goes one frame, keys without bytes are released
+    // (kitty protocol tells releases itself)
+    pub fn tick(&mut self, game: &mut Game) {
+        if self.kitty {
+            return;
+        }
+        for key in [Key::Left, Key::Right, Key::SoftDrop] {
+            let state = &mut self.keys[key as usize];
+            state.idle_frames = state.idle_frames.saturating_add(1);
+            if state.held && state.idle_frames > REPEAT_GAP {
+                state.held = false;
+                game.release(key);
+            }
+            if state.taps > 0 && state.idle_frames > REPEAT_DELAY {
+                state.taps = 0;
+            }
+        }
+    }
+}
+
+// One key from the start of bytes: (used bytes, key code, event)
+// (None if the sequence is not complete yet)
+fn parse(bytes: &[u8]) -> Option<(usize, u32, Event)> {
+    if bytes[0] != 27 {
+        return Some((1, bytes[0] as u32, Event::Press));
+    }
+    if bytes.len() < 2 {
+        return None;
+    }
+    if bytes[1] != b'[' {
+        return Some((1, 27, Event::Press));
+    }
+
+    // CSI: "27 91 params final", params are "code;modifiers:event"
+    let end = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))? + 2;
+    let (mut fields, mut field, mut sub) = ([[0u32; 2]; 2], 0, 0);
+    for &b in &bytes[2..end] {
+        match b {
+            b'0'..=b'9' if field < 2 && sub < 2 => {
+                let n = &mut fields[field][sub];
+                *n = n.saturating_mul(10).saturating_add((b - b'0') as u32);
+            }
+            b';' => (field, sub) = (field + 1, 0),
+            b':' => sub += 1,
+            _ => (),
+        }
+    }
+
+    let code = match bytes[end] {
+        b'u' => fields[0][0],
+        // Arrays keep their legacy final byte
+        final_byte @ b'A'..=b'D' => ARRAY + final_byte as u32,
+        _ => 0,
+    };
+    let modifiers = fields[1][0].saturating_sub(1);
+    let event = match fields[1][1] {
+        2 => Event::Repeat,
+        3 => Event::Release,
+        _ => Event::Press,
+    };
+    let code = if bytes[end] == b'u' && matches!(code, 99 | 122) && modifiers & 4 != 0 {
+        CTRL + code
+    } else {
+        code
+    };
+    Some((end + 1, code, event))
+}
+
+// End of primary device attributes answer "27 91 ? ... c"
+fn find_da(bytes: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i + 2 < bytes.len() {
+        if bytes[i] == 27 && bytes[i + 1] == b'[' && bytes[i + 2] == b'?' {
+            let rest = &bytes[i + 3..];
+            let end = rest
+                .iter()
+                .position(|b| !(b.is_ascii_digit() || *b == b';'))?;
+            if rest[end] == b'c' {
+                return Some(i + 3 + end + 1);
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+// Kitty flags answer "27 91 ? flags u"
+fn find_kitty(bytes: &[u8]) -> bool {
+    bytes.windows(3).enumerate().any(|(i, w)| {
+        w == b"\x1b[?"
+            && bytes[i + 3..]
+                .iter()
+                .find(|b| !b.is_ascii_digit())
+                .is_some_and(|&b| b == b'u')
+    })
+}
+
+// Is there something to read on stdin (waits up to usec)
+fn wait_stdin(usec: i64) -> bool {
+    unsafe {
+        let mut tv = timeval {
+            tv_sec: 0,
+            tv_usec: usec,
+        };
+        let mut fds = core::mem::zeroed::<fd_set>();
+        FD_ZERO(&mut fds);
+        FD_SET(STDIN_FILENO, &mut fds);
+        // Signals break the wait (and leave fds as they were)
+        let ready = select(
+            STDIN_FILENO + 1,
+            &mut fds,
+            core::ptr::null_mut(),
+            core::ptr::null_mut(),
+            &mut tv,
+        );
+        ready > 0 && FD_ISSET(STDIN_FILENO, &fds as *const fd_set)
+    }
+}
+
+fn send(bytes: &[u8]) {
+    term::send(STDOUT_FILENO, bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tetris::Ruleset;
+
+    fn game() -> Game {
+        Game::new(10, 20, 1, Ruleset::guideline())
+    }
+
+    // Frames go for the keys and the game
+    fn frames(input: &mut Input, game: &mut Game, frames: u32) {
+        for _ in 0..frames {
+            input.tick(game);
+            game.tick();
+        }
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(parse(b"x"), Some((1, 120, Event::Press)));
+        assert_eq!(parse(b"\x1b[D"), Some((3, LEFT, Event::Press)));
+        assert_eq!(parse(b"\x1b[1;1:3D"), Some((8, LEFT, Event::Release)));
+        assert_eq!(parse(b"\x1b[1;1:2D"), Some((8, LEFT, Event::Repeat)));
+        assert_eq!(parse(b"\x1b[99;5u"), Some((7, CTRL_C, Event::Press)));
+        // Only the first key
+        assert_eq!(parse(b"\x1b[Ax"), Some((3, UP, Event::Press)));
+    }
+
+    #[test]
+    fn parse_waits_for_the_end() {
+        assert_eq!(parse(b"\x1b"), None);
+        assert_eq!(parse(b"\x1b["), None);
+        assert_eq!(parse(b"\x1b[1;1:3"), None);
+    }
+
+    #[test]
+    fn device_attributes() {
+        let answer = b"\x1b[?62;22c";
+        assert_eq!(find_da(answer), Some(answer.len()));
+        assert!(!find_kitty(answer));
+
+        let answer = b"\x1b[?0u\x1b[?62;22c";
+        assert_eq!(find_da(answer), Some(answer.len()));
+        assert!(find_kitty(answer));
+
+        assert_eq!(find_da(b"\x1b[?0u\x1b[?62;2"), None);
+    }
+
+    #[test]
+    fn two_taps_move_two_cells() {
+        let (mut input, mut game) = (Input::new(), game());
+        let x = game.current_shape.x;
+        input.hit(Key::Left, &mut game);
+        frames(&mut input, &mut game, 12);
+        input.hit(Key::Left, &mut game);
+        frames(&mut input, &mut game, 30);
+        assert_eq!(game.current_shape.x, x - 2);
+        assert!(!input.keys[Key::Left as usize].held);
+    }
+
+    #[test]
+    fn autorepeat_holds_key() {
+        let (mut input, mut game) = (Input::new(), game());
+        let x = game.current_shape.x;
+        input.hit(Key::Right, &mut game);
+        frames(&mut input, &mut game, 30);
+        input.hit(Key::Right, &mut game);
+        frames(&mut input, &mut game, 2);
+        input.hit(Key::Right, &mut game);
+        assert!(input.keys[Key::Right as usize].held);
+        // Held since the first byte: DAS is charged
+        assert_eq!(game.current_shape.x, x + 2);
+        frames(&mut input, &mut game, 1);
+        assert_eq!(game.current_shape.x, x + 3);
+
+        // Bytes stop: released
+        frames(&mut input, &mut game, REPEAT_GAP + 1);
+        assert!(!input.keys[Key::Right as usize].held);
+    }
+}
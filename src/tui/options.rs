@@ -0,0 +1,202 @@
+// Import libc types
+extern crate libc;
+use libc::{c_char, time_t};
+
+use core::ffi::CStr;
+
+use crate::tui::clock::frames;
+use crate::tui::theme::{Depth, Theme};
+
+// Import game engine
+use tetris::{
+    DasCharge, GravityCurve, Handling, LineGravity, LockReset, Randomizer, RotationSystem, Ruleset,
+};
+
+#[cfg(target_os = "linux")]
+#[link(name = "c")]
+extern "C" {
+    fn time(time: *mut time_t) -> time_t;
+}
+
+// Command line options
+// (times are in ms, the engine gets them in frames)
+//   --seed N          replay the game with seed N
+//   --rules NAME      guideline, tgm, classic
+//   --rotation NAME   srs, ars, nes (instead of the one of rules)
+//   --randomizer NAME random, bag7, bag14, tgm, tgm2 (same)
+//   --next N          count of shown next shapes (1..6)
+//   --lock-delay MS   time on the ground before lock
+//   --lock-reset R    resets limit N, step or infinite
+//   --level N         start level
+//   --gravity NAME    guideline, classic, 20g
+//   --line-gravity G  naive, sticky or cascade (chunks fall after clears)
+//   --das MS          hold time before the shape slides
+//   --arr MS          time between slides (0 to the wall at once)
+//   --das-charge C    keep or reset charged DAS for the next shape
+//   --soft-drop MS    time between rows of held soft drop
+//   --ghost on|off    show where the shape will land
+//   --partial-lock-out on|off  game over when a shape locks partly
+//                     above the visible field
+//   --color C         auto, none, 16, 256 or truecolor (auto reads
+//                     NO_COLOR, COLORTERM and TERM)
+//   --theme NAME      classic, monochrome, high-contrast, colorblind
+pub struct Options {
+    pub seed: u64,
+    pub rules: Ruleset,
+    pub handling: Handling,
+    pub ghost: bool,
+    pub depth: Depth,
+    pub theme: Theme,
+}
+
+impl Options {
+    pub fn parse(argc: isize, argv: *const *const u8) -> Self {
+        let mut options = Self {
+            seed: unsafe { time(core::ptr::null_mut()) as u64 },
+            rules: Ruleset::guideline(),
+            handling: Handling::default(),
+            ghost: true,
+            depth: Depth::detect(),
+            theme: Theme::classic(),
+        };
+
+        let mut i = 1;
+        while i + 1 < argc {
+            let (name, value) = unsafe { (arg(argv, i), arg(argv, i + 1)) };
+            match name {
+                b"--seed" => {
+                    if let Some(seed) = number(value) {
+                        options.seed = seed;
+                    }
+                }
+                b"--rules" => {
+                    if let Some(rules) = Ruleset::from_name(value) {
+                        options.rules = rules;
+                    }
+                }
+                b"--rotation" => {
+                    if let Some(rotation) = RotationSystem::from_name(value) {
+                        options.rules.rotation = rotation;
+                    }
+                }
+                b"--randomizer" => {
+                    if let Some(randomizer) = Randomizer::from_name(value) {
+                        options.rules.randomizer = randomizer;
+                    }
+                }
+                b"--next" => {
+                    if let Some(next) = number(value) {
+                        options.rules.next = next as usize;
+                    }
+                }
+                b"--lock-delay" => {
+                    if let Some(ms) = number(value) {
+                        options.rules.lock_delay = frames(ms);
+                    }
+                }
+                b"--lock-reset" => {
+                    if let Some(reset) = LockReset::from_name(value) {
+                        options.rules.lock_reset = reset;
+                    }
+                }
+                b"--level" => {
+                    if let Some(level) = number(value) {
+                        options.rules.start_level = level as usize;
+                    }
+                }
+                b"--gravity" => {
+                    if let Some(gravity) = GravityCurve::from_name(value) {
+                        options.rules.gravity = gravity;
+                    }
+                }
+                b"--line-gravity" => {
+                    if let Some(gravity) = LineGravity::from_name(value) {
+                        options.rules.line_gravity = gravity;
+                    }
+                }
+                b"--das" => {
+                    if let Some(ms) = number(value) {
+                        options.handling.das = frames(ms);
+                    }
+                }
+                b"--arr" => {
+                    if let Some(ms) = number(value) {
+                        options.handling.arr = frames(ms);
+                    }
+                }
+                b"--das-charge" => {
+                    if let Some(charge) = DasCharge::from_name(value) {
+                        options.handling.das_charge = charge;
+                    }
+                }
+                b"--soft-drop" => {
+                    if let Some(ms) = number(value) {
+                        options.handling.soft_drop = frames(ms);
+                    }
+                }
+                b"--ghost" => match value {
+                    b"on" => options.ghost = true,
+                    b"off" => options.ghost = false,
+                    _ => (),
+                },
+                b"--color" => {
+                    if let Some(depth) = Depth::from_name(value) {
+                        options.depth = depth;
+                    }
+                }
+                b"--theme" => {
+                    if let Some(theme) = Theme::from_name(value) {
+                        options.theme = theme;
+                    }
+                }
+                b"--partial-lock-out" => match value {
+                    b"on" => options.rules.partial_lock_out = true,
+                    b"off" => options.rules.partial_lock_out = false,
+                    _ => (),
+                },
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            }
+            i += 2;
+        }
+
+        options
+    }
+}
+
+unsafe fn arg<'a>(argv: *const *const u8, i: isize) -> &'a [u8] {
+    CStr::from_ptr(*argv.offset(i) as *const c_char).to_bytes()
+}
+
+// Decimal number (None if it isn't one or it is too big)
+fn number(s: &[u8]) -> Option<u64> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut n = 0u64;
+    for c in s {
+        if !c.is_ascii_digit() {
+            return None;
+        }
+        n = n.checked_mul(10)?.checked_add((c - b'0') as u64)?;
+    }
+    Some(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number(b"0"), Some(0));
+        assert_eq!(number(b"42"), Some(42));
+        assert_eq!(number(b"18446744073709551615"), Some(u64::MAX));
+        assert_eq!(number(b"18446744073709551616"), None);
+        assert_eq!(number(b"99999999999999999999999"), None);
+        assert_eq!(number(b""), None);
+        assert_eq!(number(b"4x"), None);
+    }
+}
@@ -0,0 +1,214 @@
+// Import libc types
+extern crate libc;
+use libc::STDOUT_FILENO;
+
+use core::fmt::{self, Write};
+
+use crate::tui::term;
+use crate::tui::theme::{Depth, Rgb};
+
+// Biggest drawn terminal area (the rest is cut)
+pub const COLS: usize = 80;
+pub const LINES: usize = 64;
+
+// One terminal cell (None color is the default of the terminal)
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    ch: u8,
+    color: Option<Rgb>,
+}
+
+const BLANK: Cell = Cell {
+    ch: b' ',
+    color: None,
+};
+
+type Cells = [[Cell; COLS]; LINES];
+
+// Frame drawing without flicker: a frame is drawn to the back buffer,
+// flush() sends only cells which differ from the front buffer
+// (what the terminal shows) in a single write
+pub struct Renderer {
+    front: Cells,
+    back: Cells,
+    // Terminal content is unknown, draw everything
+    full: bool,
+    depth: Depth,
+    // Terminal size (up to COLS * LINES)
+    cols: usize,
+    lines: usize,
+    // Pen position and color for fmt::Write
+    x: usize,
+    y: usize,
+    left: usize,
+    color: Option<Rgb>,
+}
+
+impl Renderer {
+    pub fn new(depth: Depth) -> Self {
+        Self {
+            front: [[BLANK; COLS]; LINES],
+            back: [[BLANK; COLS]; LINES],
+            full: true,
+            depth,
+            cols: COLS,
+            lines: LINES,
+            x: 0,
+            y: 0,
+            left: 0,
+            color: None,
+        }
+    }
+
+    // Draw only what fits the terminal, everything again with the next flush
+    pub fn resize(&mut self, size: Option<(usize, usize)>) {
+        let (cols, lines) = size.unwrap_or((COLS, LINES));
+        self.cols = cols.min(COLS);
+        self.lines = lines.min(LINES);
+        self.invalidate();
+    }
+
+    // Redraw everything with the next flush (terminal content was lost)
+    pub fn invalidate(&mut self) {
+        self.full = true;
+    }
+
+    // Start a new frame (empty back buffer, pen at 0; 0)
+    pub fn clear(&mut self) {
+        self.back = [[BLANK; COLS]; LINES];
+        self.at(0, 0);
+        self.color = None;
+    }
+
+    // Color of the next text
+    pub fn color(&mut self, color: Option<Rgb>) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    // Move pen to (x; y), new lines start from x
+    pub fn at(&mut self, x: usize, y: usize) -> &mut Self {
+        self.x = x;
+        self.y = y;
+        self.left = x;
+        self
+    }
+
+    // Put text at the pen
+    pub fn text(&mut self, text: &[u8]) {
+        for &ch in text {
+            if ch == b'\n' {
+                self.x = self.left;
+                self.y += 1;
+                continue;
+            }
+            if self.x < self.cols && self.y < self.lines {
+                let color = self.color;
+                self.back[self.y][self.x] = Cell { ch, color };
+            }
+            self.x += 1;
+        }
+    }
+
+    // Send changed cells to the terminal
+    pub fn flush(&mut self) {
+        let mut out = Out::new();
+        if self.full {
+            // Cleared terminal shows blanks
+            out.push(b"\x1b[2J");
+            self.front = [[BLANK; COLS]; LINES];
+            self.full = false;
+        }
+
+        // Cursor is unknown at the start, color is the default
+        let mut cursor = None;
+        let mut color = None;
+        if self.depth != Depth::None {
+            out.push(b"\x1b[0m");
+        }
+        for y in 0..LINES {
+            for x in 0..COLS {
+                let cell = self.back[y][x];
+                if cell == self.front[y][x] {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    let _ = write!(out, "\x1b[{};{}H", y + 1, x + 1);
+                }
+                if cell.color != color && self.depth != Depth::None {
+                    match cell.color {
+                        Some(rgb) => {
+                            let _ = self.depth.sgr(rgb, &mut out);
+                        }
+                        None => out.push(b"\x1b[0m"),
+                    }
+                    color = cell.color;
+                }
+                out.push(&[cell.ch]);
+                cursor = Some((x + 1, y));
+            }
+        }
+        if color.is_some() {
+            out.push(b"\x1b[0m");
+        }
+        // Park the cursor under the frame
+        let _ = write!(out, "\x1b[{};1H", self.lines.min(self.used_lines() + 1));
+
+        self.front = self.back;
+        out.send();
+    }
+
+    // Lines with something drawn
+    fn used_lines(&self) -> usize {
+        let blank = [BLANK; COLS];
+        LINES
+            - self
+                .back
+                .iter()
+                .rev()
+                .take_while(|&&line| line == blank)
+                .count()
+    }
+}
+
+impl Write for Renderer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.text(s.as_bytes());
+        Ok(())
+    }
+}
+
+// Bytes of one frame (sent at once, or in parts if it is too big)
+struct Out {
+    buf: [u8; 16384],
+    len: usize,
+}
+
+impl Out {
+    fn new() -> Self {
+        Self {
+            buf: [0; 16384],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        if self.len + bytes.len() > self.buf.len() {
+            self.send();
+        }
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn send(&mut self) {
+        term::send(STDOUT_FILENO, &self.buf[..self.len]);
+        self.len = 0;
+    }
+}
+
+impl Write for Out {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+// Import libc types
+extern crate libc;
+use libc::{
+    c_int, c_ulong, c_void, sighandler_t, size_t, ssize_t, termios, winsize, ECHO, ICANON, SIGCONT,
+    SIGHUP, SIGINT, SIGSTOP, SIGTERM, SIGTSTP, SIGWINCH, SIG_DFL, STDIN_FILENO, STDOUT_FILENO,
+    TCSANOW, TIOCGWINSZ,
+};
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Link libc funcs
+#[cfg(target_os = "linux")]
+#[link(name = "c")]
+extern "C" {
+    fn tcgetattr(fd: c_int, termios: *mut termios) -> c_int;
+    fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const termios) -> c_int;
+    fn write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t;
+    fn signal(signum: c_int, handler: sighandler_t) -> sighandler_t;
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    pub fn raise(sig: c_int) -> c_int;
+    fn _exit(status: c_int) -> !;
+}
+
+// Alternate screen on, cursor hidden (and back)
+const ENTER: &[u8] = b"\x1b[?1049h\x1b[?25l";
+const LEAVE: &[u8] = b"\x1b[0m\x1b[?25h\x1b[?1049l";
+// Kitty progressive enhancement flags:
+// 1 disambiguate escape codes, 2 report press/repeat/release
+pub const KITTY_ON: &[u8] = b"\x1b[>3u";
+// Pop kitty keyboard flags
+pub const KITTY_OFF: &[u8] = b"\x1b[<u";
+
+// Terminal settings before the game (restored on any exit) and of the game
+static mut SAVED: termios = unsafe { core::mem::zeroed() };
+static mut RAW: termios = unsafe { core::mem::zeroed() };
+// Game owns the terminal now
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+// Kitty keyboard flags are pushed (see Input)
+pub static KITTY: AtomicBool = AtomicBool::new(false);
+// Terminal was given back for Ctrl+Z
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+// Main loop has to see: process went on after a stop, terminal resized
+static RESUMED: AtomicBool = AtomicBool::new(false);
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+// Raw keys without echo, alternate screen, hidden cursor
+// (signals which end the game give the terminal back first,
+// Ctrl+Z gives it back until the game goes on)
+pub fn enter() {
+    unsafe {
+        let (saved, raw) = (&raw mut SAVED, &raw mut RAW);
+        tcgetattr(STDIN_FILENO, saved);
+        *raw = *saved;
+        (*raw).c_lflag &= !(ICANON | ECHO);
+    }
+    apply(false);
+    ACTIVE.store(true, Ordering::SeqCst);
+
+    let handlers: [(c_int, extern "C" fn(c_int)); 6] = [
+        (SIGINT, on_exit_signal),
+        (SIGTERM, on_exit_signal),
+        (SIGHUP, on_exit_signal),
+        (SIGTSTP, on_stop),
+        (SIGCONT, on_continue),
+        (SIGWINCH, on_resize),
+    ];
+    for (sig, handler) in handlers {
+        unsafe {
+            signal(sig, handler as sighandler_t);
+        }
+    }
+}
+
+// Game settings of the terminal
+fn apply(kitty: bool) {
+    unsafe {
+        tcsetattr(STDIN_FILENO, TCSANOW, &raw const RAW);
+    }
+    send(STDOUT_FILENO, ENTER);
+    if kitty {
+        send(STDOUT_FILENO, KITTY_ON);
+    }
+}
+
+// Settings of the terminal before the game
+fn leave(kitty: bool) {
+    if kitty {
+        send(STDOUT_FILENO, KITTY_OFF);
+    }
+    send(STDOUT_FILENO, LEAVE);
+    unsafe {
+        tcsetattr(STDIN_FILENO, TCSANOW, &raw const SAVED);
+    }
+}
+
+// Give back termios, cursor and screen (once, safe in signal handlers)
+pub fn restore() {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    leave(KITTY.swap(false, Ordering::SeqCst));
+}
+
+// Process went on after a stop since the last call
+// (the game was paused, frames and held keys are stale)
+pub fn resumed() -> bool {
+    RESUMED.swap(false, Ordering::SeqCst)
+}
+
+// Terminal was resized since the last call
+pub fn resized() -> bool {
+    RESIZED.swap(false, Ordering::SeqCst)
+}
+
+// Terminal size (columns, lines), None if it is unknown
+pub fn size() -> Option<(usize, usize)> {
+    let mut size = unsafe { core::mem::zeroed::<winsize>() };
+    let ok = unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ as c_ulong, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some((size.ws_col as usize, size.ws_row as usize))
+}
+
+// Ctrl+Z: give the terminal back and stop
+// (SIGSTOP stops at once even here, SIGCONT takes the terminal again)
+extern "C" fn on_stop(_sig: c_int) {
+    if ACTIVE.load(Ordering::SeqCst) {
+        leave(KITTY.load(Ordering::SeqCst));
+        SUSPENDED.store(true, Ordering::SeqCst);
+    }
+    unsafe {
+        raise(SIGSTOP);
+    }
+}
+
+// Going on after any stop (the shell may have changed termios too)
+extern "C" fn on_continue(_sig: c_int) {
+    if ACTIVE.load(Ordering::SeqCst) {
+        if SUSPENDED.swap(false, Ordering::SeqCst) {
+            apply(KITTY.load(Ordering::SeqCst));
+        } else {
+            unsafe {
+                tcsetattr(STDIN_FILENO, TCSANOW, &raw const RAW);
+            }
+        }
+    }
+    RESUMED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn on_resize(_sig: c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+// Restore the terminal, then die by the signal as without handler
+extern "C" fn on_exit_signal(sig: c_int) {
+    restore();
+    unsafe {
+        signal(sig, SIG_DFL);
+        raise(sig);
+    }
+}
+
+// Restore the terminal and print panic message to stderr
+#[cfg(not(test))]
+pub fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    use core::fmt::{self, Write};
+
+    struct Stderr;
+    impl Write for Stderr {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            send(libc::STDERR_FILENO, s.as_bytes());
+            Ok(())
+        }
+    }
+
+    restore();
+    let _ = writeln!(Stderr, "{}", info);
+    unsafe { _exit(101) }
+}
+
+// Write all bytes to fd (safe in signal handlers)
+pub fn send(fd: c_int, bytes: &[u8]) {
+    let mut sent = 0;
+    while sent < bytes.len() {
+        let rest = &bytes[sent..];
+        let n = unsafe { write(fd, rest.as_ptr() as *const c_void, rest.len()) };
+        if n <= 0 {
+            break;
+        }
+        sent += n as usize;
+    }
+}
@@ -0,0 +1,180 @@
+// Import libc types
+extern crate libc;
+use libc::c_char;
+
+use core::ffi::CStr;
+
+// Import game engine
+use tetris::Shapes;
+
+// Link libc funcs
+#[cfg(target_os = "linux")]
+#[link(name = "c")]
+extern "C" {
+    fn getenv(name: *const c_char) -> *const c_char;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+// Colors the terminal can show
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    None,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+// Standard 16 colors (xterm), for the nearest one
+#[rustfmt::skip]
+const ANSI16: [Rgb; 16] = [
+    Rgb(0, 0, 0), Rgb(205, 0, 0), Rgb(0, 205, 0), Rgb(205, 205, 0),
+    Rgb(0, 0, 238), Rgb(205, 0, 205), Rgb(0, 205, 205), Rgb(229, 229, 229),
+    Rgb(127, 127, 127), Rgb(255, 0, 0), Rgb(0, 255, 0), Rgb(255, 255, 0),
+    Rgb(92, 92, 255), Rgb(255, 0, 255), Rgb(0, 255, 255), Rgb(255, 255, 255),
+];
+
+impl Depth {
+    // NO_COLOR turns colors off, COLORTERM and TERM tell the rest
+    pub fn detect() -> Self {
+        if env(c"NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return Self::None;
+        }
+        if matches!(env(c"COLORTERM"), Some(b"truecolor" | b"24bit")) {
+            return Self::TrueColor;
+        }
+        match env(c"TERM") {
+            Some(term) if term.windows(8).any(|w| w == b"256color") => Self::Ansi256,
+            Some(b"dumb") | None => Self::None,
+            Some(_) => Self::Ansi16,
+        }
+    }
+
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"auto" => Some(Self::detect()),
+            b"none" => Some(Self::None),
+            b"16" => Some(Self::Ansi16),
+            b"256" => Some(Self::Ansi256),
+            b"truecolor" => Some(Self::TrueColor),
+            _ => None,
+        }
+    }
+
+    // Escape sequence of foreground color
+    pub fn sgr(self, color: Rgb, out: &mut impl core::fmt::Write) -> core::fmt::Result {
+        let Rgb(r, g, b) = color;
+        match self {
+            Self::None => Ok(()),
+            Self::TrueColor => write!(out, "\x1b[38;2;{};{};{}m", r, g, b),
+            Self::Ansi256 => {
+                // 6 * 6 * 6 color cube from 16
+                let level = |c: u8| (c as u32 * 5 + 127) / 255;
+                let index = 16 + 36 * level(r) + 6 * level(g) + level(b);
+                write!(out, "\x1b[38;5;{}m", index)
+            }
+            Self::Ansi16 => {
+                let distance = |c: &Rgb| {
+                    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                    d(c.0, r) + d(c.1, g) + d(c.2, b)
+                };
+                let index = (0..16).min_by_key(|&i| distance(&ANSI16[i])).unwrap_or(7);
+                let code = if index < 8 {
+                    30 + index
+                } else {
+                    90 + index - 8
+                };
+                write!(out, "\x1b[{}m", code)
+            }
+        }
+    }
+}
+
+// Colors of shapes (in Shapes order) and of the ghost
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub shapes: [Rgb; 7],
+    pub ghost: Rgb,
+}
+
+impl Theme {
+    // Guideline colors: T purple, I cyan, O yellow, S green, Z red,
+    // J blue, L orange
+    pub fn classic() -> Self {
+        Self {
+            shapes: [
+                Rgb(160, 0, 240),
+                Rgb(0, 240, 240),
+                Rgb(240, 240, 0),
+                Rgb(0, 240, 0),
+                Rgb(240, 0, 0),
+                Rgb(0, 0, 240),
+                Rgb(240, 160, 0),
+            ],
+            ghost: Rgb(128, 128, 128),
+        }
+    }
+
+    pub fn monochrome() -> Self {
+        Self {
+            shapes: [Rgb(208, 208, 208); 7],
+            ghost: Rgb(128, 128, 128),
+        }
+    }
+
+    // Brightest colors, far from each other
+    pub fn high_contrast() -> Self {
+        Self {
+            shapes: [
+                Rgb(255, 0, 255),
+                Rgb(0, 255, 255),
+                Rgb(255, 255, 0),
+                Rgb(0, 255, 0),
+                Rgb(255, 0, 0),
+                Rgb(92, 92, 255),
+                Rgb(255, 255, 255),
+            ],
+            ghost: Rgb(160, 160, 160),
+        }
+    }
+
+    // Okabe-Ito palette, kept apart with any color vision
+    pub fn colorblind() -> Self {
+        Self {
+            shapes: [
+                Rgb(204, 121, 167),
+                Rgb(86, 180, 233),
+                Rgb(240, 228, 66),
+                Rgb(0, 158, 115),
+                Rgb(213, 94, 0),
+                Rgb(0, 114, 178),
+                Rgb(230, 159, 0),
+            ],
+            ghost: Rgb(128, 128, 128),
+        }
+    }
+
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"classic" => Some(Self::classic()),
+            b"monochrome" => Some(Self::monochrome()),
+            b"high-contrast" => Some(Self::high_contrast()),
+            b"colorblind" => Some(Self::colorblind()),
+            _ => None,
+        }
+    }
+
+    pub fn shape(&self, shape: Shapes) -> Rgb {
+        self.shapes[shape as usize]
+    }
+}
+
+// Value of environment variable
+fn env(name: &CStr) -> Option<&'static [u8]> {
+    let value = unsafe { getenv(name.as_ptr()) };
+    if value.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(value) }.to_bytes())
+}